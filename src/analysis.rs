@@ -0,0 +1,287 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::path::Path;
+use tracing::debug;
+
+use crate::{locator_distance_km, TransmissionRecord};
+
+// Spots are reported to the hertz within the 200 Hz WSPR window, so
+// anything within this of our frequency is taken to be us.
+const FREQUENCY_TOLERANCE_HERTZ: f64 = 200.;
+
+/// One row of a WSPRnet spot archive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spot {
+    // Start of the 2 minute slot.
+    pub time: DateTime<Utc>,
+    pub reporter: String,
+    pub reporter_grid: String,
+    pub snr: i32,
+    pub frequency_hertz: f64,
+    pub call: String,
+    pub grid: String,
+    pub dbm: i32,
+}
+
+impl Spot {
+    // Archive columns: id, unix time, reporter, reporter grid, SNR,
+    // MHz, call, grid, dBm, drift, km, azimuth, band, version, code.
+    // Only the ones needed are read, so older dumps with fewer trailing
+    // columns still parse.
+    fn from_record(record: &csv::StringRecord) -> Result<Self> {
+        let field = |i: usize| -> Result<&str> {
+            record
+                .get(i)
+                .map(str::trim)
+                .with_context(|| format!("Missing column {i}"))
+        };
+        let timestamp: i64 = field(1)?.parse().context("Bad timestamp")?;
+        let Some(time) = DateTime::from_timestamp(timestamp, 0) else {
+            bail!("Timestamp {timestamp} out of range");
+        };
+        Ok(Self {
+            time,
+            reporter: field(2)?.to_string(),
+            reporter_grid: field(3)?.to_string(),
+            snr: field(4)?.parse().context("Bad SNR")?,
+            frequency_hertz: field(5)?.parse::<f64>().context("Bad frequency")? * 1e6,
+            call: field(6)?.to_string(),
+            grid: field(7)?.to_string(),
+            dbm: field(8)?.parse().context("Bad power")?,
+        })
+    }
+}
+
+/// Read a WSPRnet CSV spot dump. Rows that don't parse, e.g. a
+/// header, are skipped.
+pub fn read_spots(path: &Path) -> Result<Vec<Spot>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut spots = Vec::new();
+    for (line, record) in reader.records().enumerate() {
+        match Spot::from_record(&record?) {
+            Ok(spot) => spots.push(spot),
+            Err(err) => debug!("{}:{}: skipping: {err:#}", path.display(), line + 1),
+        }
+    }
+    Ok(spots)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnrStats {
+    pub min: i32,
+    pub max: i32,
+    pub mean: f64,
+}
+
+impl SnrStats {
+    fn new(snrs: &[i32]) -> Option<Self> {
+        Some(Self {
+            min: *snrs.iter().min()?,
+            max: *snrs.iter().max()?,
+            mean: snrs.iter().sum::<i32>() as f64 / snrs.len() as f64,
+        })
+    }
+}
+
+/// How a transmission was heard.
+#[derive(Debug, Clone)]
+pub struct TransmissionAnalysis {
+    pub record: TransmissionRecord,
+    pub spots: Vec<Spot>,
+    pub reporters: usize,
+    // Furthest reporter and its distance, if our locator is known.
+    pub best_distance_km: Option<f64>,
+    pub best_reporter: Option<String>,
+    pub snr: Option<SnrStats>,
+}
+
+impl fmt::Display for TransmissionAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:>5} {:>3} reporters",
+            self.record.start.format("%Y-%m-%d %H:%M"),
+            self.record.band.map(|band| band.name()).unwrap_or("?"),
+            self.reporters
+        )?;
+        if let (Some(distance), Some(reporter)) = (self.best_distance_km, &self.best_reporter) {
+            write!(f, ", best {distance:.0} km ({reporter})")?;
+        }
+        if let Some(snr) = &self.snr {
+            write!(f, ", SNR {} / {:.1} / {} dB", snr.min, snr.mean, snr.max)?;
+        }
+        Ok(())
+    }
+}
+
+/// The call sign without prefix, suffix or the brackets of a hashed
+/// call, e.g. "K1ABC" for "PA/K1ABC", "K1ABC/7" or "<K1ABC/P>".
+pub fn base_call(call: &str) -> String {
+    call.trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .split('/')
+        // The call itself is the longest part.
+        .fold("", |longest, part| {
+            if part.len() > longest.len() {
+                part
+            } else {
+                longest
+            }
+        })
+        .to_ascii_uppercase()
+}
+
+/// Match spots of `call` to our transmissions by slot, call sign and
+/// frequency (or band, for records without one). Calls are compared
+/// by `base_call`, as spots may show a prefix, suffix or hashed call.
+/// Distances are from the locator in each record, or `grid` if it has
+/// none.
+pub fn analyze(
+    transmissions: &[TransmissionRecord],
+    spots: &[Spot],
+    call: &str,
+    grid: Option<&str>,
+) -> Vec<TransmissionAnalysis> {
+    let call = base_call(call);
+    let mut by_slot: HashMap<DateTime<Utc>, Vec<&Spot>> = HashMap::new();
+    for spot in spots.iter().filter(|spot| base_call(&spot.call) == call) {
+        by_slot.entry(spot.time).or_default().push(spot);
+    }
+    transmissions
+        .iter()
+        .map(|record| {
+            let slot = record
+                .start
+                .duration_trunc(TimeDelta::minutes(2))
+                .unwrap_or(record.start);
+            let frequency = record.frequency_hertz.or_else(|| {
+                record
+                    .band
+                    .and_then(|band| band.wspr_frequency())
+                    .map(|hertz| hertz as f64)
+            });
+            let matched: Vec<Spot> = by_slot
+                .get(&slot)
+                .into_iter()
+                .flatten()
+                .filter(|spot| {
+                    frequency.is_some_and(|hertz| {
+                        (spot.frequency_hertz - hertz).abs() <= FREQUENCY_TOLERANCE_HERTZ
+                    })
+                })
+                .map(|spot| (*spot).clone())
+                .collect();
+            analyze_one(record, matched, record.locator.as_deref().or(grid))
+        })
+        .collect()
+}
+
+fn analyze_one(
+    record: &TransmissionRecord,
+    spots: Vec<Spot>,
+    grid: Option<&str>,
+) -> TransmissionAnalysis {
+    let reporters = spots
+        .iter()
+        .map(|spot| spot.reporter.as_str())
+        .collect::<BTreeSet<_>>()
+        .len();
+    let best = grid.and_then(|grid| {
+        spots
+            .iter()
+            .filter_map(|spot| {
+                let distance = locator_distance_km(grid, &spot.reporter_grid).ok()?;
+                Some((distance, spot.reporter.clone()))
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+    });
+    let snrs: Vec<i32> = spots.iter().map(|spot| spot.snr).collect();
+    TransmissionAnalysis {
+        record: record.clone(),
+        reporters,
+        best_distance_km: best.as_ref().map(|(distance, _)| *distance),
+        best_reporter: best.map(|(_, reporter)| reporter),
+        snr: SnrStats::new(&snrs),
+        spots,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Band;
+    use chrono::TimeZone;
+
+    fn spot(minute: u32, reporter: &str, grid: &str, snr: i32, hertz: f64, call: &str) -> Spot {
+        Spot {
+            time: Utc.with_ymd_and_hms(2024, 1, 1, 0, minute, 0).unwrap(),
+            reporter: reporter.to_string(),
+            reporter_grid: grid.to_string(),
+            snr,
+            frequency_hertz: hertz,
+            call: call.to_string(),
+            grid: "JO01".to_string(),
+            dbm: 23,
+        }
+    }
+
+    fn record(minute: u32) -> TransmissionRecord {
+        TransmissionRecord {
+            start: Utc.with_ymd_and_hms(2024, 1, 1, 0, minute, 1).unwrap(),
+            end: None,
+            band: Some(Band::B20m),
+            frequency_hertz: Some(14_097_100.),
+            filter_bank: None,
+            symbols: None,
+            gps_locked: None,
+            locator: Some("JO01".to_string()),
+        }
+    }
+
+    #[test]
+    fn base_call_strips_prefix_suffix_and_hash_brackets() {
+        assert_eq!(base_call("k1abc"), "K1ABC");
+        assert_eq!(base_call("PA/K1ABC"), "K1ABC");
+        assert_eq!(base_call("K1ABC/7"), "K1ABC");
+        assert_eq!(base_call("<K1ABC/P>"), "K1ABC");
+        assert_eq!(base_call("<...>"), "...");
+    }
+
+    #[test]
+    fn analyze_joins_spots_by_slot_call_and_frequency() {
+        let spots = [
+            spot(2, "G0AAA", "IO91", -20, 14_097_150., "K1ABC"),
+            spot(2, "DL1BBB", "JO62", -10, 14_097_050., "PA/K1ABC"),
+            spot(2, "DL1BBB", "JO62", -12, 14_097_060., "<K1ABC>"),
+            // Another station, another slot, another frequency.
+            spot(2, "F1CCC", "JN18", -5, 14_097_100., "K2XYZ"),
+            spot(4, "F1CCC", "JN18", -5, 14_097_100., "K1ABC"),
+            spot(2, "F1CCC", "JN18", -5, 14_097_400., "K1ABC"),
+        ];
+        let analysis = analyze(&[record(2)], &spots, "K1ABC", None);
+        assert_eq!(analysis.len(), 1);
+        let analysis = &analysis[0];
+        assert_eq!(analysis.spots.len(), 3);
+        assert_eq!(analysis.reporters, 2);
+        assert_eq!(analysis.best_reporter.as_deref(), Some("DL1BBB"));
+        let snr = analysis.snr.unwrap();
+        assert_eq!((snr.min, snr.max), (-20, -10));
+        assert_eq!(snr.mean, -14.);
+    }
+
+    #[test]
+    fn analyze_reports_unheard_transmissions() {
+        let spots = [spot(2, "G0AAA", "IO91", -20, 14_097_150., "K1ABC")];
+        let analysis = analyze(&[record(6)], &spots, "K1ABC", None);
+        assert_eq!(analysis[0].reporters, 0);
+        assert!(analysis[0].snr.is_none());
+        assert!(analysis[0].best_distance_km.is_none());
+    }
+}
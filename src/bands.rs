@@ -0,0 +1,362 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::{BitAnd, BitOr, Not, Sub};
+use std::str::FromStr;
+
+use crate::{Band, BandTxEnable, FilterBank, LowPassFilterFactory, Response, ZachtekDevice};
+
+impl Band {
+    // Bands the unit can transmit on, numbered 0-15 in OBD/TBN/FLP.
+    pub const TX_BANDS: [Band; 16] = [
+        Band::B2190m,
+        Band::B630m,
+        Band::B160m,
+        Band::B80m,
+        Band::B40m,
+        Band::B30m,
+        Band::B20m,
+        Band::B17m,
+        Band::B15m,
+        Band::B12m,
+        Band::B10m,
+        Band::B6m,
+        Band::B4m,
+        Band::B2m,
+        Band::B70Cm,
+        Band::B23Cm,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Band::B2190m => "2190m",
+            Band::B630m => "630m",
+            Band::B160m => "160m",
+            Band::B80m => "80m",
+            Band::B40m => "40m",
+            Band::B30m => "30m",
+            Band::B20m => "20m",
+            Band::B17m => "17m",
+            Band::B15m => "15m",
+            Band::B12m => "12m",
+            Band::B10m => "10m",
+            Band::B6m => "6m",
+            Band::B4m => "4m",
+            Band::B2m => "2m",
+            Band::B70Cm => "70cm",
+            Band::B23Cm => "23cm",
+            Band::NoFilter => "link",
+            Band::Open => "open",
+        }
+    }
+
+    /// WSPR transmit frequency (dial + 1500 Hz) in Hertz.
+    pub fn wspr_frequency(&self) -> Option<u64> {
+        match self {
+            Band::B2190m => Some(137_500),
+            Band::B630m => Some(475_700),
+            Band::B160m => Some(1_838_100),
+            Band::B80m => Some(3_570_100),
+            Band::B40m => Some(7_040_100),
+            Band::B30m => Some(10_140_200),
+            Band::B20m => Some(14_097_100),
+            Band::B17m => Some(18_106_100),
+            Band::B15m => Some(21_096_100),
+            Band::B12m => Some(24_926_100),
+            Band::B10m => Some(28_126_100),
+            Band::B6m => Some(50_294_500),
+            Band::B4m => Some(70_092_500),
+            Band::B2m => Some(144_490_500),
+            Band::B70Cm => Some(432_301_500),
+            Band::B23Cm => Some(1_296_501_500),
+            Band::NoFilter | Band::Open => None,
+        }
+    }
+
+    pub fn is_tx_band(&self) -> bool {
+        (*self as u8) < 16
+    }
+}
+
+impl fmt::Display for Band {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// By name, e.g. "20m", for logs.
+impl Serialize for Band {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Band {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl FromStr for Band {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim().to_ascii_lowercase();
+        match Band::TX_BANDS.iter().find(|band| band.name() == s) {
+            Some(band) => Ok(*band),
+            None => bail!("Unknown band '{s}'"),
+        }
+    }
+}
+
+/// Set of transmit bands, one bit per band number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct BandSet {
+    bits: u16,
+}
+
+impl BandSet {
+    pub const EMPTY: BandSet = BandSet { bits: 0 };
+    pub const ALL: BandSet = BandSet { bits: u16::MAX };
+
+    pub const fn from_bits(bits: u16) -> Self {
+        Self { bits }
+    }
+
+    pub fn bits(&self) -> u16 {
+        self.bits
+    }
+
+    pub fn insert(&mut self, band: Band) {
+        if band.is_tx_band() {
+            self.bits |= 1 << band as u8;
+        }
+    }
+
+    pub fn remove(&mut self, band: Band) {
+        if band.is_tx_band() {
+            self.bits &= !(1 << band as u8);
+        }
+    }
+
+    pub fn contains(&self, band: Band) -> bool {
+        band.is_tx_band() && self.bits & (1 << band as u8) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Band> + '_ {
+        Band::TX_BANDS
+            .iter()
+            .copied()
+            .filter(|band| self.contains(*band))
+    }
+
+    /// Enabled bands from a set of OBD responses.
+    pub fn from_tx_enables(enables: &[BandTxEnable]) -> Self {
+        enables
+            .iter()
+            .filter(|enable| enable.enabled)
+            .map(|enable| enable.band)
+            .collect()
+    }
+
+    /// Bands with a matching filter fitted, from FLP responses. The
+    /// link (98) and open (99) banks are not counted.
+    pub fn from_filters(filters: &[LowPassFilterFactory]) -> Self {
+        filters.iter().map(|filter| filter.band).collect()
+    }
+}
+
+impl FromIterator<Band> for BandSet {
+    fn from_iter<I: IntoIterator<Item = Band>>(iter: I) -> Self {
+        let mut set = BandSet::EMPTY;
+        for band in iter {
+            set.insert(band);
+        }
+        set
+    }
+}
+
+impl BitAnd for BandSet {
+    type Output = BandSet;
+
+    fn bitand(self, rhs: BandSet) -> BandSet {
+        BandSet::from_bits(self.bits & rhs.bits)
+    }
+}
+
+impl BitOr for BandSet {
+    type Output = BandSet;
+
+    fn bitor(self, rhs: BandSet) -> BandSet {
+        BandSet::from_bits(self.bits | rhs.bits)
+    }
+}
+
+impl Sub for BandSet {
+    type Output = BandSet;
+
+    fn sub(self, rhs: BandSet) -> BandSet {
+        BandSet::from_bits(self.bits & !rhs.bits)
+    }
+}
+
+impl Not for BandSet {
+    type Output = BandSet;
+
+    fn not(self) -> BandSet {
+        BandSet::from_bits(!self.bits)
+    }
+}
+
+impl fmt::Display for BandSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.iter().map(|band| band.name()).collect();
+        f.write_str(&names.join(","))
+    }
+}
+
+fn band_number(band: Band) -> String {
+    format!("{:02}", band as u8)
+}
+
+impl ZachtekDevice {
+    pub fn read_band_tx_enable(&mut self, band: Band) -> Result<BandTxEnable> {
+        self.query(
+            BandTxEnable::CODE,
+            band_number(band).as_bytes(),
+            |response| match response {
+                Response::BandTxEnable(enable) if enable.band == band => Some(enable.clone()),
+                _ => None,
+            },
+        )
+    }
+
+    pub fn write_band_tx_enable(&mut self, band: Band, enabled: bool) -> Result<()> {
+        self.warn_if_band_unsupported(band);
+        let args = format!("{} {}", band_number(band), if enabled { 'E' } else { 'D' });
+        self.set_verified(BandTxEnable::CODE, args.as_bytes())
+    }
+
+    /// Read the enable flag of every band the unit supports.
+    pub fn read_band_set(&mut self) -> Result<BandSet> {
+        let mut set = BandSet::EMPTY;
+        for band in self.capabilities().bands.iter() {
+            if self.read_band_tx_enable(band)?.enabled {
+                set.insert(band);
+            }
+        }
+        Ok(set)
+    }
+
+    /// Write the enable flag of every band the unit supports, and any
+    /// others in `set`, enabling exactly `set`.
+    pub fn write_band_set(&mut self, set: BandSet) -> Result<()> {
+        for band in (self.capabilities().bands | set).iter() {
+            self.write_band_tx_enable(band, set.contains(band))?;
+        }
+        Ok(())
+    }
+
+    pub fn read_filter(&mut self, filter_bank: FilterBank) -> Result<LowPassFilterFactory> {
+        self.warn_if_filter_bank_unsupported(filter_bank);
+        self.query(
+            LowPassFilterFactory::CODE,
+            &[filter_bank.into()],
+            |response| match response {
+                Response::LowPassFilterFactory(filter) if filter.filter_bank == filter_bank => {
+                    Some(filter.clone())
+                }
+                _ => None,
+            },
+        )
+    }
+
+    /// Read the filter fitted in each of the unit's banks.
+    pub fn read_filters(&mut self) -> Result<Vec<LowPassFilterFactory>> {
+        self.capabilities()
+            .filter_banks
+            .iter()
+            .map(|filter_bank| self.read_filter(*filter_bank))
+            .collect()
+    }
+
+    /// Enabled bands that also have a matching filter fitted.
+    pub fn read_usable_bands(&mut self) -> Result<BandSet> {
+        let enabled = self.read_band_set()?;
+        let filters = self.read_filters()?;
+        Ok(enabled & BandSet::from_filters(&filters))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_tx_bands_are_stored() {
+        let mut set = BandSet::EMPTY;
+        set.insert(Band::B20m);
+        set.insert(Band::NoFilter);
+        set.insert(Band::Open);
+        assert_eq!(set.len(), 1);
+        assert!(set.contains(Band::B20m));
+        assert!(!set.contains(Band::NoFilter));
+        assert!(!BandSet::ALL.contains(Band::Open));
+        set.remove(Band::B20m);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn iterates_in_band_order() {
+        let set: BandSet = [Band::B10m, Band::B2190m, Band::B40m].into_iter().collect();
+        let bands: Vec<Band> = set.iter().collect();
+        assert_eq!(bands, vec![Band::B2190m, Band::B40m, Band::B10m]);
+        assert_eq!(set.bits(), 1 | 1 << 4 | 1 << 10);
+    }
+
+    #[test]
+    fn set_operations() {
+        let a: BandSet = [Band::B20m, Band::B40m].into_iter().collect();
+        let b: BandSet = [Band::B40m, Band::B80m].into_iter().collect();
+        assert_eq!((a & b).iter().collect::<Vec<_>>(), vec![Band::B40m]);
+        assert_eq!((a | b).len(), 3);
+        assert_eq!((a - b).iter().collect::<Vec<_>>(), vec![Band::B20m]);
+        assert!(!(!a).contains(Band::B20m));
+        assert!((!a).contains(Band::B80m));
+    }
+
+    #[test]
+    fn from_tx_enables_keeps_enabled_bands() {
+        let enables = [
+            BandTxEnable {
+                band: Band::B20m,
+                enabled: true,
+            },
+            BandTxEnable {
+                band: Band::B30m,
+                enabled: false,
+            },
+        ];
+        let set = BandSet::from_tx_enables(&enables);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![Band::B20m]);
+    }
+
+    #[test]
+    fn band_names_round_trip() {
+        for band in Band::TX_BANDS {
+            assert_eq!(band.name().parse::<Band>().unwrap(), band);
+        }
+        assert_eq!(" 20M ".parse::<Band>().unwrap(), Band::B20m);
+        assert!("link".parse::<Band>().is_err());
+    }
+}
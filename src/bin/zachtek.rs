@@ -1,17 +1,20 @@
-use anyhow::{Context, Result};
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use clap::{Parser, Subcommand};
+use std::io::{self, BufRead, Write};
 use std::num::ParseIntError;
-use std::time::Duration;
-use tracing::Level;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{warn, Level};
 use tracing_subscriber::FmtSubscriber;
 use zachtek::*;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Serial port.
+    /// Serial port. Found by probing USB serial ports if not given.
     #[arg(short, long)]
-    port: String,
+    port: Option<String>,
 
     /// Tracing level.
     #[arg(short, long, default_value_t=Level::INFO)]
@@ -24,42 +27,760 @@ struct Args {
     /// Poll sleep interval (seconds).
     #[arg(long, value_parser = parse_duration_in_seconds, default_value="10")]
     poll_sleep_interval: Duration,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Match a transmission log against a WSPRnet spot dump and report
+    /// how each transmission was heard. Doesn't use the unit.
+    Analyze {
+        /// Transmission log written by `record` (.csv or JSON lines).
+        transmissions: PathBuf,
+
+        /// WSPRnet CSV spot archive.
+        spots: PathBuf,
+
+        /// Call sign the unit sends.
+        #[arg(long)]
+        call: String,
+
+        /// Locator to measure distances from when the log has none.
+        #[arg(long)]
+        grid: Option<String>,
+    },
+
+    /// List the protocol's codes, what can be done with them and
+    /// whether the unit keeps them across power cycles.
+    Codes,
+
+    /// Probe USB serial ports and list the units found.
+    List,
+
+    #[command(flatten)]
+    Device(DeviceCommand),
+}
+
+// Commands that talk to a unit.
+#[derive(Subcommand, Debug)]
+enum DeviceCommand {
+    /// Poll the unit and print its responses (default).
+    Monitor,
+
+    /// List transmit bands, optionally enabling or disabling some.
+    Bands {
+        /// Bands to enable (e.g. 20m,40m).
+        #[arg(long, value_delimiter = ',')]
+        enable: Vec<Band>,
+
+        /// Bands to disable (e.g. 2190m,630m).
+        #[arg(long, value_delimiter = ',')]
+        disable: Vec<Band>,
+    },
+
+    /// Calibrate the reference oscillator (FRF) against a frequency
+    /// counter.
+    Calibrate {
+        /// Carrier to generate and measure (Hz).
+        #[arg(long, default_value_t = 10_000_000.)]
+        frequency: f64,
+
+        /// Measured carrier (Hz). Asked for if neither this nor
+        /// --counter-file is given.
+        #[arg(long, conflicts_with = "counter_file")]
+        measured: Option<f64>,
+
+        /// Frequency counter log; new readings are averaged.
+        #[arg(long)]
+        counter_file: Option<PathBuf>,
+    },
+
+    /// Show the GPS constellations in use, optionally setting them or
+    /// trying each in turn to find what works best at the site.
+    Constellation {
+        /// gps, beidou or all.
+        #[arg(long, conflicts_with = "compare")]
+        set: Option<Constellation>,
+
+        /// Try each setting, resetting the unit in between.
+        #[arg(long)]
+        compare: bool,
+
+        /// How long to try each setting for (seconds).
+        #[arg(long, value_parser = parse_duration_in_seconds, default_value = "600")]
+        duration: Duration,
+    },
+
+    /// Check that every enabled band has a suitable low pass filter.
+    Filters,
+
+    /// Poll the unit for a while and report on its health.
+    Health {
+        /// How long to watch the unit (seconds).
+        #[arg(long, value_parser = parse_duration_in_seconds, default_value = "60")]
+        duration: Duration,
+
+        /// Report VCC at or below this as a brown-out (mV).
+        #[arg(long, default_value_t = 3000)]
+        brown_out: u32,
+
+        /// Report VCC further than this from 3300 mV (mV).
+        #[arg(long, default_value_t = 150)]
+        max_deviation: u32,
+
+        /// Reset the unit first, to measure GPS time to first fix.
+        #[arg(long)]
+        reboot: bool,
+
+        /// An external reference is connected; report the unit running
+        /// on its internal one as a fallback.
+        #[arg(long)]
+        external_reference: bool,
+    },
+
+    /// Show the location source and manual locator, optionally changing
+    /// them.
+    Location {
+        /// gps or manual.
+        #[arg(long)]
+        source: Option<LocationSource>,
+
+        /// New manual locator (4 or 6 characters).
+        #[arg(long, conflicts_with = "position")]
+        locator: Option<String>,
+
+        /// New manual position as latitude,longitude (degrees).
+        #[arg(long, value_parser = parse_position, allow_hyphen_values = true)]
+        position: Option<LatLon>,
+
+        /// Then compare the manual locator with the GPS for this long
+        /// (seconds).
+        #[arg(long, value_parser = parse_duration_in_seconds)]
+        watch: Option<Duration>,
+
+        /// Warn when the manual and GPS locators are further apart than
+        /// this (km).
+        #[arg(long, default_value_t = DEFAULT_MAX_LOCATOR_DISTANCE_KM)]
+        max_distance: f64,
+    },
+
+    /// Put the unit to sleep.
+    Pause {
+        /// How long to sleep (seconds, up to 4000000).
+        #[arg(value_parser = parse_duration_in_seconds)]
+        duration: Duration,
+    },
+
+    /// Print the spots receivers could report for each transmission, in
+    /// WSPRnet/wsprd column order. Compound calls and 6 character
+    /// locators give one line per alternating message type.
+    Spots {
+        /// csv or jsonl; aligned text if not given.
+        #[arg(long)]
+        format: Option<LogFormat>,
+
+        /// File to append to; standard output if not given.
+        #[arg(long)]
+        output: Option<PathBuf>,
+
+        /// Altitude of the unit (m), sent instead of power in altitude
+        /// mode.
+        #[arg(long)]
+        altitude: Option<u32>,
+    },
+
+    /// Log every transmission as it happens.
+    Record {
+        /// csv or jsonl.
+        #[arg(long, default_value = "csv")]
+        format: LogFormat,
+
+        /// File to append to; standard output if not given.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Reset the unit and report what it prints while booting.
+    Reboot {
+        /// How long to wait for the unit (seconds).
+        #[arg(long, value_parser = parse_duration_in_seconds, default_value = "30")]
+        deadline: Duration,
+    },
+
+    /// Follow the GPS position and say what triggered each transmission,
+    /// for units in tracker mode.
+    Track {
+        /// GPX file to write the track to, rewritten as it grows.
+        #[arg(long)]
+        gpx: Option<PathBuf>,
+    },
+
+    /// Show the TX pause, optionally setting it.
+    TxPause {
+        /// New pause (minutes, up to 99999).
+        #[arg(long)]
+        set: Option<u32>,
+    },
 }
 
 fn parse_duration_in_seconds(arg: &str) -> Result<Duration, ParseIntError> {
     Ok(Duration::from_secs(arg.parse()?))
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+fn parse_position(arg: &str) -> Result<LatLon> {
+    let Some((lat, lon)) = arg.split_once(',') else {
+        bail!("Expected latitude,longitude");
+    };
+    LatLon::new(lat.trim().parse()?, lon.trim().parse()?)
+}
 
-    let subscriber = FmtSubscriber::builder().with_max_level(args.level).finish();
-    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+fn find_port(timeout: Duration) -> Result<String> {
+    let mut found = discover(timeout)?;
+    match found.len() {
+        0 => bail!("No units found, use --port"),
+        1 => Ok(found.remove(0).port_name),
+        n => bail!("{n} units found, use --port to pick one"),
+    }
+}
+
+fn list(timeout: Duration) -> Result<()> {
+    for device in discover(timeout)? {
+        println!("{device}");
+    }
+    Ok(())
+}
+
+// Where calibration measurements come from.
+enum Counter {
+    Prompt,
+    // Log file and how many readings of it were already used.
+    File(PathBuf, usize),
+}
+
+impl Counter {
+    fn measure(&mut self, prompt: &str) -> Result<f64> {
+        match self {
+            Counter::Prompt => {
+                let line = ask(&format!("{prompt}, measured frequency (Hz): "))?;
+                Ok(line.trim().parse()?)
+            }
+            Counter::File(path, used) => {
+                ask(&format!(
+                    "{prompt}, log it to {} and press Enter",
+                    path.display()
+                ))?;
+                let readings = read_counter_file(path)?;
+                let new = readings.get(*used..).unwrap_or_default();
+                *used = readings.len();
+                mean_frequency(new)
+            }
+        }
+    }
+}
+
+fn ask(prompt: &str) -> Result<String> {
+    print!("{prompt}");
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    Ok(line)
+}
+
+fn calibrate(
+    device: &mut ZachtekDevice,
+    frequency: f64,
+    measured: Option<f64>,
+    counter_file: Option<PathBuf>,
+) -> Result<()> {
+    let mut counter = match counter_file {
+        Some(path) => {
+            let used = read_counter_file(&path)
+                .map(|readings| readings.len())
+                .unwrap_or(0);
+            Counter::File(path, used)
+        }
+        None => Counter::Prompt,
+    };
+    let mut calibration = device.begin_calibration(frequency)?;
+    println!(
+        "Generating {} Hz, FRF {}",
+        calibration.signal_hertz, calibration.reference_hertz
+    );
+    let result = (|| {
+        let measured = match measured {
+            Some(measured) => measured,
+            None => counter.measure("Measure the output")?,
+        };
+        let step = device.apply_calibration(&mut calibration, measured)?;
+        println!("{step}");
+        let verified = counter.measure("Measure the corrected output")?;
+        println!(
+            "Now {verified:.2} Hz ({:+.3} ppm)",
+            calibration.error_ppm(verified)
+        );
+        Ok(())
+    })();
+    let restored = device
+        .end_calibration(calibration)
+        .context("Failed to restore the generator frequency and mode");
+    match result {
+        Err(err) => {
+            if let Err(restore_err) = restored {
+                warn!("{restore_err:#}");
+            }
+            Err(err)
+        }
+        Ok(()) => restored,
+    }
+}
+
+fn codes() {
+    println!("Code  Category         S/G  Storage   Name");
+    for info in CODES {
+        println!("{info}");
+    }
+}
 
-    let port_path = args.port;
-    let baud_rate = 9_600;
-    let mut port = serialport::new(&port_path, baud_rate)
-        .data_bits(serialport::DataBits::Eight)
-        .parity(serialport::Parity::None)
-        .stop_bits(serialport::StopBits::One)
-        .flow_control(serialport::FlowControl::None)
-        .timeout(args.timeout)
-        .open()
-        .with_context(|| format!("Failed to open serial port at {}", port_path))?;
-
-    let mut device = ZachtekDevice::new(&mut port);
-
-    device.set_run()?;
-    device.start_poll_thread(args.poll_sleep_interval);
+fn analyze_log(transmissions: &Path, spots: &Path, call: &str, grid: Option<&str>) -> Result<()> {
+    let records: Vec<TransmissionRecord> =
+        read_records(LogFormat::for_path(transmissions), transmissions)?;
+    let spots = read_spots(spots)?;
+    let analyses = analyze(&records, &spots, call, grid);
+    for analysis in &analyses {
+        println!("{analysis}");
+    }
+    let heard = analyses
+        .iter()
+        .filter(|analysis| analysis.reporters > 0)
+        .count();
+    println!("{heard} of {} transmissions heard", analyses.len());
+    Ok(())
+}
+
+fn monitor(device: &mut ZachtekDevice, poll_sleep_interval: Duration) -> Result<()> {
+    let mut voltage = VoltageMonitor::default();
+    let mut gps = GpsTracker::new();
+    let mut locator = LocatorMonitor::default();
+    device.start_poll_thread(poll_sleep_interval)?;
     device.clear_input()?;
     loop {
-        match device.read_response() {
-            Ok(response) => {
+        match device.read_event()? {
+            Some(Event::Response(response)) => {
                 println!("{response:?}");
+                if let Some(event) = voltage.check(&response, Utc::now()) {
+                    println!("{event}");
+                }
+                if let Some(event) = gps.check(&response, Utc::now()) {
+                    println!("{event}");
+                }
+                if let Some(mismatch) = locator.check(&response, Utc::now()) {
+                    println!("{mismatch}");
+                }
+            }
+            Some(Event::Error(err)) => {
+                println!("Err: {err}");
+            }
+            Some(Event::Disconnected(err)) => {
+                println!("Disconnected: {err}");
+            }
+            Some(Event::Reconnected(Some(info))) => {
+                println!("Reconnected: {info}");
+            }
+            Some(Event::Reconnected(None)) => {
+                println!("Reconnected");
+            }
+            Some(Event::Resumed) => {
+                println!("Resumed");
+            }
+            None => {}
+        }
+    }
+}
+
+fn bands(device: &mut ZachtekDevice, enable: &[Band], disable: &[Band]) -> Result<()> {
+    let mut enabled = device.read_band_set()?;
+    if !enable.is_empty() || !disable.is_empty() {
+        let wanted =
+            (enabled | enable.iter().copied().collect()) - disable.iter().copied().collect();
+        for band in ((wanted - enabled) | (enabled - wanted)).iter() {
+            device.write_band_tx_enable(band, wanted.contains(band))?;
+        }
+        enabled = device.read_band_set()?;
+    }
+    let filtered = BandSet::from_filters(&device.read_filters()?);
+    for band in device.capabilities().bands.iter() {
+        println!(
+            "{:>5}  {:<8}  {}",
+            band.name(),
+            if enabled.contains(band) {
+                "enabled"
+            } else {
+                "disabled"
+            },
+            if filtered.contains(band) {
+                "filter"
+            } else {
+                ""
             }
-            Err(err) => {
-                println!("Err: {err:?}");
+        );
+    }
+    Ok(())
+}
+
+fn constellation(
+    device: &mut ZachtekDevice,
+    set: Option<Constellation>,
+    compare: bool,
+    duration: Duration,
+) -> Result<()> {
+    if compare {
+        let trials = device.compare_constellations(duration)?;
+        for trial in &trials {
+            println!("{trial}");
+        }
+        match best_constellation(&trials) {
+            Some(best) => println!("Best: {}", best.constellation),
+            None => println!("No setting got a fix"),
+        }
+        return Ok(());
+    }
+    if let Some(constellation) = set {
+        device.write_constellation(constellation)?;
+    }
+    println!("Constellation: {}", device.read_constellation()?);
+    Ok(())
+}
+
+fn filters(device: &mut ZachtekDevice) -> Result<()> {
+    let check = device.check_filters()?;
+    print!("{check}");
+    if !check.is_ok() {
+        bail!("Filter configuration is not safe to transmit with");
+    }
+    Ok(())
+}
+
+fn health(
+    device: &mut ZachtekDevice,
+    poll_sleep_interval: Duration,
+    duration: Duration,
+    thresholds: VoltageThresholds,
+    reboot: bool,
+    external_reference: bool,
+) -> Result<()> {
+    let mut monitor = HealthMonitor::new();
+    monitor.reference = ReferenceMonitor::new(external_reference);
+    monitor.voltage = VoltageMonitor::new(thresholds);
+    if reboot {
+        let report = device.reboot(Duration::from_secs(30))?;
+        monitor.gps.reset(report.reset_time);
+        for (time, response) in report.replay() {
+            monitor.update(response, *time);
+        }
+    }
+    device.start_poll_thread(poll_sleep_interval)?;
+    device.clear_input()?;
+    let started = Instant::now();
+    while started.elapsed() < duration {
+        match device.read_event()? {
+            Some(Event::Response(response)) => monitor.update(&response, Utc::now()),
+            Some(Event::Error(err)) => println!("Err: {err}"),
+            Some(Event::Disconnected(err)) => println!("Disconnected: {err}"),
+            Some(Event::Reconnected(Some(info))) => println!("Reconnected: {info}"),
+            Some(Event::Reconnected(None)) => println!("Reconnected"),
+            Some(Event::Resumed) => println!("Resumed"),
+            None => {}
+        }
+    }
+    device.stop_poll_thread();
+    print!("{}", monitor.report(Utc::now()));
+    Ok(())
+}
+
+fn location(
+    device: &mut ZachtekDevice,
+    source: Option<LocationSource>,
+    locator: Option<String>,
+    position: Option<LatLon>,
+    watch: Option<Duration>,
+    max_distance: f64,
+) -> Result<()> {
+    if let Some(locator) = locator {
+        device.write_manual_locator(&locator)?;
+    }
+    if let Some(position) = position {
+        device.write_manual_position(position)?;
+    }
+    if let Some(source) = source {
+        device.write_location_source(source)?;
+    }
+    let source = device.read_location_source()?;
+    let manual = device.read_manual_locator()?;
+    println!("Location source: {source:?}");
+    println!("Manual locator: {manual}");
+    let Some(watch) = watch else {
+        return Ok(());
+    };
+    let mut monitor = LocatorMonitor::new(max_distance);
+    monitor.manual_6 = Some(manual);
+    device.clear_input()?;
+    let started = Instant::now();
+    while started.elapsed() < watch {
+        if let Some(Event::Response(response)) = device.read_event()? {
+            if let Some(mismatch) = monitor.check(&response, Utc::now()) {
+                println!("{mismatch}");
+            }
+        }
+    }
+    match (monitor.gps(), monitor.distance_km()) {
+        (Some(gps), Some(distance)) => println!("GPS locator: {gps} ({distance:.0} km away)"),
+        _ => println!("GPS locator: unknown"),
+    }
+    Ok(())
+}
+
+// Append to `path`, or write to stdout. The CSV header is only written
+// to a new file.
+fn open_log(format: LogFormat, path: Option<PathBuf>) -> Result<RecordWriter<Box<dyn Write>>> {
+    let Some(path) = path else {
+        return Ok(RecordWriter::new(format, Box::new(io::stdout())));
+    };
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let header = file.metadata()?.len() == 0;
+    Ok(RecordWriter::with_header(format, Box::new(file), header))
+}
+
+fn record(
+    device: &mut ZachtekDevice,
+    poll_sleep_interval: Duration,
+    format: LogFormat,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let mut writer = open_log(format, output)?;
+    let mut recorder = TransmissionRecorder::new();
+    device.start_poll_thread(poll_sleep_interval)?;
+    device.clear_input()?;
+    loop {
+        match device.read_event()? {
+            Some(Event::Response(response)) => {
+                if let Some(record) = recorder.update(&response, Utc::now()) {
+                    writer.write(&record)?;
+                }
             }
+            Some(Event::Disconnected(_)) => {
+                // Whatever was in progress won't be finished.
+                if let Some(record) = recorder.take_current() {
+                    writer.write(&record)?;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn spots(
+    device: &mut ZachtekDevice,
+    poll_sleep_interval: Duration,
+    format: Option<LogFormat>,
+    output: Option<PathBuf>,
+    altitude_meters: Option<u32>,
+) -> Result<()> {
+    let mut station = device.read_station()?;
+    station.altitude_meters = altitude_meters;
+    if station.dbm().is_none() {
+        warn!("Unit sends its altitude as power; give --altitude to show it");
+    }
+    let mut writer = match format {
+        Some(format) => Some(open_log(format, output)?),
+        None if output.is_some() => bail!("--output needs --format"),
+        None => None,
+    };
+    let mut recorder = TransmissionRecorder::new();
+    device.start_poll_thread(poll_sleep_interval)?;
+    device.clear_input()?;
+    loop {
+        let Some(Event::Response(response)) = device.read_event()? else {
+            continue;
+        };
+        let Some(record) = recorder.update(&response, Utc::now()) else {
+            continue;
+        };
+        for spot in ExpectedSpot::alternatives(&station, &record) {
+            match writer.as_mut() {
+                Some(writer) => writer.write(&spot)?,
+                None => println!("{spot}"),
+            }
+        }
+    }
+}
+
+fn track(
+    device: &mut ZachtekDevice,
+    poll_sleep_interval: Duration,
+    gpx: Option<PathBuf>,
+) -> Result<()> {
+    if device.read_time_slot()? != TimeSlot::Tracker {
+        println!("Unit is not in tracker mode; only hourly transmissions will match");
+    }
+    let mut track = PositionTrack::new();
+    let mut recorder = TransmissionRecorder::new();
+    let mut last_start = None;
+    device.start_poll_thread(poll_sleep_interval)?;
+    device.clear_input()?;
+    loop {
+        let Some(Event::Response(response)) = device.read_event()? else {
+            continue;
+        };
+        let now = Utc::now();
+        let points = track.points.len();
+        if let Some(movement) = track.check(&response, now) {
+            println!("{movement}");
+        }
+        if track.points.len() > points {
+            if let Some(path) = &gpx {
+                let file = std::fs::File::create(path)
+                    .with_context(|| format!("Failed to create {}", path.display()))?;
+                track.write_gpx(io::BufWriter::new(file))?;
+            }
+        }
+        if let Some(record) = recorder.update(&response, now) {
+            let trigger = track.trigger(&record, last_start);
+            last_start = Some(record.start);
+            println!("{}", TrackerTransmission { record, trigger });
+        }
+    }
+}
+
+fn tx_pause(device: &mut ZachtekDevice, set: Option<u32>) -> Result<()> {
+    if let Some(minutes) = set {
+        device.write_tx_pause(Duration::from_secs(60 * minutes as u64))?;
+    }
+    println!(
+        "TX pause: {} minutes",
+        device.read_tx_pause()?.as_secs() / 60
+    );
+    Ok(())
+}
+
+fn reboot(device: &mut ZachtekDevice, deadline: Duration) -> Result<()> {
+    let report = device.reboot(deadline)?;
+    println!("Booted in {:?}", report.boot_time);
+    for line in &report.other_output {
+        println!("{line}");
+    }
+    for (time, response) in &report.responses {
+        let offset = (*time - report.reset_time).to_std().unwrap_or_default();
+        println!("{offset:>10.3?} {response:?}");
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let mut args = Args::parse();
+
+    let subscriber = FmtSubscriber::builder().with_max_level(args.level).finish();
+    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+
+    let command = args.command.take();
+    match command.unwrap_or(Command::Device(DeviceCommand::Monitor)) {
+        Command::Analyze {
+            transmissions,
+            spots,
+            call,
+            grid,
+        } => analyze_log(&transmissions, &spots, &call, grid.as_deref()),
+        Command::Codes => {
+            codes();
+            Ok(())
+        }
+        Command::List => list(args.timeout),
+        Command::Device(command) => run_device_command(&args, command),
+    }
+}
+
+fn run_device_command(args: &Args, command: DeviceCommand) -> Result<()> {
+    let port_path = match &args.port {
+        Some(port_path) => port_path.clone(),
+        None => find_port(args.timeout)?,
+    };
+    let mut device = ZachtekDevice::open(&port_path, args.timeout)?;
+    // Monitoring works without knowing which unit it is.
+    if !matches!(
+        command,
+        DeviceCommand::Monitor | DeviceCommand::Health { .. }
+    ) {
+        match device.handshake() {
+            Ok(info) => println!("{info}"),
+            Err(err) => warn!("Could not identify the unit: {err:#}"),
+        }
+    }
+
+    match command {
+        DeviceCommand::Monitor => monitor(&mut device, args.poll_sleep_interval),
+        DeviceCommand::Bands { enable, disable } => bands(&mut device, &enable, &disable),
+        DeviceCommand::Calibrate {
+            frequency,
+            measured,
+            counter_file,
+        } => calibrate(&mut device, frequency, measured, counter_file),
+        DeviceCommand::Constellation {
+            set,
+            compare,
+            duration,
+        } => constellation(&mut device, set, compare, duration),
+        DeviceCommand::Filters => filters(&mut device),
+        DeviceCommand::Health {
+            duration,
+            brown_out,
+            max_deviation,
+            reboot,
+            external_reference,
+        } => health(
+            &mut device,
+            args.poll_sleep_interval,
+            duration,
+            VoltageThresholds {
+                brown_out_millivolts: brown_out,
+                deviation_millivolts: max_deviation,
+                ..VoltageThresholds::default()
+            },
+            reboot,
+            external_reference,
+        ),
+        DeviceCommand::Location {
+            source,
+            locator,
+            position,
+            watch,
+            max_distance,
+        } => location(&mut device, source, locator, position, watch, max_distance),
+        DeviceCommand::Pause { duration } => {
+            device.pause(duration)?;
+            println!("Paused for {duration:?}");
+            Ok(())
+        }
+        DeviceCommand::Record { format, output } => {
+            record(&mut device, args.poll_sleep_interval, format, output)
         }
+        DeviceCommand::Reboot { deadline } => reboot(&mut device, deadline),
+        DeviceCommand::Spots {
+            format,
+            output,
+            altitude,
+        } => spots(
+            &mut device,
+            args.poll_sleep_interval,
+            format,
+            output,
+            altitude,
+        ),
+        DeviceCommand::Track { gpx } => track(&mut device, args.poll_sleep_interval, gpx),
+        DeviceCommand::TxPause { set } => tx_pause(&mut device, set),
     }
 }
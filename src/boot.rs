@@ -0,0 +1,113 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+use crate::{is_timeout, process_line, Response, ZachtekDevice};
+
+// Output is taken to be over once the unit has been quiet this long
+// after its first valid response.
+const BOOT_QUIET_TIME: Duration = Duration::from_millis(1500);
+
+/// What the unit said while booting.
+#[derive(Debug, Clone, Default)]
+pub struct BootReport {
+    // When the reset was released.
+    pub reset_time: DateTime<Utc>,
+    // Reset release to first valid response.
+    pub boot_time: Duration,
+    // Each with the time it arrived.
+    pub responses: Vec<(DateTime<Utc>, Response)>,
+    // Lines that weren't valid responses, e.g. a bootloader banner.
+    pub other_output: Vec<String>,
+}
+
+impl BootReport {
+    /// The unit's startup information (MIN), if it sent any.
+    pub fn microcontroller_info(&self) -> Option<&str> {
+        self.responses
+            .iter()
+            .find_map(|(_, response)| match response {
+                Response::MicrocontrollerInfo(info) => Some(info.info.as_str()),
+                _ => None,
+            })
+    }
+
+    /// Responses other than MIN, for replaying into monitors reset at
+    /// `reset_time`. They take MIN as the unit starting, which would
+    /// restart their clocks when it arrived rather than at the reset.
+    pub fn replay(&self) -> impl Iterator<Item = &(DateTime<Utc>, Response)> {
+        self.responses
+            .iter()
+            .filter(|(_, response)| !matches!(response, Response::MicrocontrollerInfo(_)))
+    }
+}
+
+impl ZachtekDevice {
+    /// Reset the unit and wait up to `deadline` for it to come back,
+    /// collecting everything it prints while starting. Polling, if
+    /// running, is paused while the unit boots.
+    pub fn reboot(&mut self, deadline: Duration) -> Result<BootReport> {
+        // Resetting wakes a paused unit.
+        self.end_pause(true);
+        let poll_sleep_interval = self.poll.as_ref().map(|poll| poll.poll_sleep_interval);
+        self.stop_poll_thread();
+        let timeout = self.port.timeout();
+        self.port
+            .set_timeout(BOOT_QUIET_TIME)
+            .context("Failed to set timeout")?;
+        let report = self.collect_boot_output(deadline);
+        self.port
+            .set_timeout(timeout)
+            .context("Failed to restore timeout")?;
+        let report = report?;
+        info!("Unit booted in {:?}", report.boot_time);
+
+        if self.info.is_some() {
+            self.handshake()?;
+        }
+        if let Some(poll_sleep_interval) = poll_sleep_interval {
+            self.start_poll_thread(poll_sleep_interval)?;
+        }
+        Ok(report)
+    }
+
+    fn collect_boot_output(&mut self, deadline: Duration) -> Result<BootReport> {
+        self.clear_input()?;
+        self.reset_device()?;
+        let started = Instant::now();
+        let mut report = BootReport {
+            reset_time: Utc::now(),
+            ..BootReport::default()
+        };
+        self.set_run()?;
+
+        let mut booted = false;
+        while started.elapsed() < deadline {
+            let line = match self.read_line() {
+                Ok(line) => line,
+                Err(err) if is_timeout(&err) && booted => break,
+                Err(err) if is_timeout(&err) => continue,
+                Err(err) => return Err(err),
+            };
+            let text = String::from_utf8_lossy(&line).into_owned();
+            match process_line(line) {
+                Ok(response) => {
+                    if !booted {
+                        booted = true;
+                        report.boot_time = started.elapsed();
+                    }
+                    report.responses.push((Utc::now(), response));
+                }
+                Err(err) => {
+                    debug!("Boot output: {text} ({err})");
+                    report.other_output.push(text);
+                }
+            }
+        }
+        if !booted {
+            bail!("Unit did not respond within {deadline:?} of reset");
+        }
+        Ok(report)
+    }
+}
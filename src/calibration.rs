@@ -0,0 +1,308 @@
+use anyhow::{bail, ensure, Context, Result};
+use std::fmt;
+use std::path::Path;
+use tracing::info;
+
+use crate::{
+    CurrentModeCommand, CurrentReferenceCommand, ExternalReferenceFrequencyData,
+    GeneratorFrequencyData, Mode, Reference, ReferenceOscillatorFrequencyFactory, Response,
+    ZachtekDevice,
+};
+
+// Largest value that fits the 9 digit FRF and DER fields.
+const MAX_REFERENCE_HERTZ: u32 = 999_999_999;
+
+// Largest value that fits the 12 digit DGF field, in centihertz.
+const MAX_GENERATOR_CENTIHERTZ: u64 = 999_999_999_999;
+
+// Corrections bigger than this are more likely a typo or a counter on
+// the wrong range than a crystal that far off.
+const MAX_CORRECTION_PPM: f64 = 1000.;
+
+/// State of a reference oscillator calibration in progress. The unit
+/// is generating a carrier at `signal_hertz` while this exists.
+#[derive(Debug, Clone)]
+pub struct ReferenceCalibration {
+    pub signal_hertz: f64,
+    // FRF the unit is currently using.
+    pub reference_hertz: u32,
+    // What to restore when calibration ends.
+    previous_mode: Mode,
+    previous_signal_centihertz: u64,
+}
+
+/// One step of calibration: what was measured and what FRF it led to.
+#[derive(Debug, Clone, Copy)]
+pub struct CalibrationStep {
+    pub measured_hertz: f64,
+    pub error_ppm: f64,
+    pub old_reference_hertz: u32,
+    pub new_reference_hertz: u32,
+}
+
+impl fmt::Display for CalibrationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "measured {:.2} Hz ({:+.3} ppm), FRF {} -> {}",
+            self.measured_hertz, self.error_ppm, self.old_reference_hertz, self.new_reference_hertz
+        )
+    }
+}
+
+impl ReferenceCalibration {
+    /// Error of a measured output against the requested one.
+    pub fn error_ppm(&self, measured_hertz: f64) -> f64 {
+        (measured_hertz - self.signal_hertz) / self.signal_hertz * 1e6
+    }
+
+    /// FRF that would bring the measured output onto frequency. The
+    /// synthesizer scales its output by nominal/actual reference, so
+    /// the actual reference is FRF * measured / requested.
+    pub fn corrected_reference(&self, measured_hertz: f64) -> Result<u32> {
+        ensure!(
+            measured_hertz.is_finite() && measured_hertz > 0.,
+            "Bad measured frequency {measured_hertz}"
+        );
+        let error_ppm = self.error_ppm(measured_hertz);
+        ensure!(
+            error_ppm.abs() <= MAX_CORRECTION_PPM,
+            "Measured {measured_hertz} Hz is {error_ppm:.1} ppm from {} Hz, check the counter",
+            self.signal_hertz
+        );
+        let corrected =
+            (self.reference_hertz as f64 * measured_hertz / self.signal_hertz).round() as u32;
+        ensure!(
+            corrected <= MAX_REFERENCE_HERTZ,
+            "Corrected FRF {corrected} does not fit in 9 digits"
+        );
+        Ok(corrected)
+    }
+}
+
+/// Read frequencies from a frequency counter log: one reading per
+/// line, optionally followed by a unit (Hz, kHz or MHz). Lines that
+/// don't start with a number, e.g. headers, are skipped.
+pub fn read_counter_file(path: &Path) -> Result<Vec<f64>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(text.lines().filter_map(parse_counter_reading).collect())
+}
+
+fn parse_counter_reading(line: &str) -> Option<f64> {
+    let mut fields = line
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .filter(|field| !field.is_empty());
+    let value: f64 = fields.next()?.parse().ok()?;
+    let scale = match fields.next().map(|unit| unit.to_ascii_lowercase()) {
+        Some(unit) if unit == "khz" => 1e3,
+        Some(unit) if unit == "mhz" => 1e6,
+        _ => 1.,
+    };
+    Some(value * scale)
+}
+
+/// Mean of counter readings, as the gate time of a single reading is
+/// often too short for sub-hertz resolution.
+pub fn mean_frequency(readings: &[f64]) -> Result<f64> {
+    if readings.is_empty() {
+        bail!("No frequency readings");
+    }
+    Ok(readings.iter().sum::<f64>() / readings.len() as f64)
+}
+
+impl ZachtekDevice {
+    pub fn read_mode(&mut self) -> Result<Mode> {
+        self.query(CurrentModeCommand::CODE, b"", |response| match response {
+            Response::CurrentModeCommand(command) => Some(command.mode),
+            _ => None,
+        })
+    }
+
+    pub fn write_mode(&mut self, mode: Mode) -> Result<()> {
+        self.set_verified(CurrentModeCommand::CODE, &[mode.into()])
+    }
+
+    pub fn read_reference(&mut self) -> Result<Reference> {
+        self.query(
+            CurrentReferenceCommand::CODE,
+            b"",
+            |response| match response {
+                Response::CurrentReferenceCommand(command) => Some(command.reference),
+                _ => None,
+            },
+        )
+    }
+
+    pub fn read_generator_centihertz(&mut self) -> Result<u64> {
+        self.query(
+            GeneratorFrequencyData::CODE,
+            b"",
+            |response| match response {
+                Response::GeneratorFrequencyData(data) => Some(data.centihertz),
+                _ => None,
+            },
+        )
+    }
+
+    pub fn write_generator_centihertz(&mut self, centihertz: u64) -> Result<()> {
+        ensure!(
+            centihertz <= MAX_GENERATOR_CENTIHERTZ,
+            "Generator frequency {centihertz} cHz does not fit in 12 digits"
+        );
+        let args = format!("{centihertz:012}");
+        self.set_verified(GeneratorFrequencyData::CODE, args.as_bytes())
+    }
+
+    pub fn read_reference_oscillator_frequency(&mut self) -> Result<u32> {
+        self.query(
+            ReferenceOscillatorFrequencyFactory::CODE,
+            b"",
+            |response| match response {
+                Response::ReferenceOscillatorFrequencyFactory(factory) => Some(factory.hertz),
+                _ => None,
+            },
+        )
+    }
+
+    pub fn write_reference_oscillator_frequency(&mut self, hertz: u32) -> Result<()> {
+        ensure!(
+            hertz <= MAX_REFERENCE_HERTZ,
+            "Reference frequency {hertz} does not fit in 9 digits"
+        );
+        let args = format!("{hertz:09}");
+        self.set_verified(ReferenceOscillatorFrequencyFactory::CODE, args.as_bytes())
+    }
+
+    pub fn read_external_reference_frequency(&mut self) -> Result<u32> {
+        self.warn_if_external_reference_unsupported();
+        self.query(
+            ExternalReferenceFrequencyData::CODE,
+            b"",
+            |response| match response {
+                Response::ExternalReferenceFrequencyData(data) => Some(data.hertz),
+                _ => None,
+            },
+        )
+    }
+
+    pub fn write_external_reference_frequency(&mut self, hertz: u32) -> Result<()> {
+        self.warn_if_external_reference_unsupported();
+        ensure!(
+            hertz <= MAX_REFERENCE_HERTZ,
+            "Reference frequency {hertz} does not fit in 9 digits"
+        );
+        let args = format!("{hertz:09}");
+        self.set_verified(ExternalReferenceFrequencyData::CODE, args.as_bytes())
+    }
+
+    /// Start generating a carrier at `signal_hertz` from the internal
+    /// reference so it can be measured.
+    pub fn begin_calibration(&mut self, signal_hertz: f64) -> Result<ReferenceCalibration> {
+        if self.read_reference()? == Reference::External {
+            bail!("Unit is using its external reference; disconnect it to calibrate FRF");
+        }
+        let centihertz = (signal_hertz * 100.).round();
+        ensure!(
+            centihertz >= 1. && centihertz <= MAX_GENERATOR_CENTIHERTZ as f64,
+            "Bad calibration frequency {signal_hertz}"
+        );
+        let calibration = ReferenceCalibration {
+            signal_hertz: centihertz / 100.,
+            reference_hertz: self.read_reference_oscillator_frequency()?,
+            previous_mode: self.read_mode()?,
+            previous_signal_centihertz: self.read_generator_centihertz()?,
+        };
+        self.write_generator_centihertz(centihertz as u64)?;
+        self.write_mode(Mode::Sig)?;
+        info!(
+            "Generating {} Hz with FRF {}",
+            calibration.signal_hertz, calibration.reference_hertz
+        );
+        Ok(calibration)
+    }
+
+    /// Write the FRF that corrects `measured_hertz` and retune the
+    /// carrier so the new value can be measured.
+    pub fn apply_calibration(
+        &mut self,
+        calibration: &mut ReferenceCalibration,
+        measured_hertz: f64,
+    ) -> Result<CalibrationStep> {
+        let new_reference_hertz = calibration.corrected_reference(measured_hertz)?;
+        let step = CalibrationStep {
+            measured_hertz,
+            error_ppm: calibration.error_ppm(measured_hertz),
+            old_reference_hertz: calibration.reference_hertz,
+            new_reference_hertz,
+        };
+        self.write_reference_oscillator_frequency(new_reference_hertz)?;
+        calibration.reference_hertz = new_reference_hertz;
+        // Setting the frequency again makes the unit recompute the
+        // synthesizer from the new FRF.
+        self.write_generator_centihertz((calibration.signal_hertz * 100.).round() as u64)?;
+        self.write_mode(Mode::Sig)?;
+        info!("Calibration: {step}");
+        Ok(step)
+    }
+
+    /// Restore the generator frequency and mode from before
+    /// calibration.
+    pub fn end_calibration(&mut self, calibration: ReferenceCalibration) -> Result<()> {
+        self.write_generator_centihertz(calibration.previous_signal_centihertz)?;
+        self.write_mode(calibration.previous_mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calibration() -> ReferenceCalibration {
+        ReferenceCalibration {
+            signal_hertz: 10_000_000.,
+            reference_hertz: 26_000_000,
+            previous_mode: Mode::Wspr,
+            previous_signal_centihertz: 0,
+        }
+    }
+
+    #[test]
+    fn corrects_reference_by_measured_ratio() {
+        let calibration = calibration();
+        assert_eq!(
+            calibration.corrected_reference(10_000_000.).unwrap(),
+            26_000_000
+        );
+        // 10 ppm high.
+        assert_eq!(
+            calibration.corrected_reference(10_000_100.).unwrap(),
+            26_000_260
+        );
+        assert!((calibration.error_ppm(10_000_100.) - 10.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn implausible_measurements_are_refused() {
+        let calibration = calibration();
+        assert!(calibration.corrected_reference(0.).is_err());
+        assert!(calibration.corrected_reference(f64::NAN).is_err());
+        // A counter on the kHz range.
+        assert!(calibration.corrected_reference(10_000.).is_err());
+    }
+
+    #[test]
+    fn counter_readings_are_scaled_by_unit() {
+        assert_eq!(parse_counter_reading("10000012.5"), Some(10_000_012.5));
+        assert_eq!(parse_counter_reading("10000.0125 kHz"), Some(10_000_012.5));
+        assert_eq!(parse_counter_reading("10.0000125,MHz"), Some(10_000_012.5));
+        assert_eq!(parse_counter_reading("Frequency;Unit"), None);
+        assert_eq!(parse_counter_reading(""), None);
+    }
+
+    #[test]
+    fn mean_needs_readings() {
+        assert_eq!(mean_frequency(&[1., 2., 3.]).unwrap(), 2.);
+        assert!(mean_frequency(&[]).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+use crate::{
+    BandTxEnable, CallSignData, ConstellationOption, CurrentModeCommand, CurrentReferenceCommand,
+    ExternalReferenceFrequencyData, GeneratorFrequencyData, HardwareRevisionFactory,
+    HardwareVersionFactory, LocationSourceOption, Locator4Data, Locator4GPS, Locator6Data,
+    Locator6GPS, LocatorPrecisionOption, LockStatusGPS, LowPassFilterFactory, LowPassFilterSet,
+    MicrocontrollerInfo, MicrocontrollerPause, MicrocontrollerVoltage, NameData, PowerData,
+    PowerEncodingOption, PrefixData, PrefixSuffixOption, ProductModelNumberFactory,
+    ReferenceOscillatorFrequencyFactory, SatelliteInfoGPS, SoftwareRevisionFactory,
+    SoftwareVersionFactory, StartModeOption, SuffixData, TimeGPS, TimeSlotOption,
+    TransmitterBandCycleComplete, TransmitterCurrentBand, TransmitterFrequency, TransmitterStatus,
+    TransmitterWSPRSymbol, TxPauseOption,
+};
+
+/// Command group, from the first letter of the code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Current,
+    Option,
+    Data,
+    Factory,
+    Gps,
+    Transmitter,
+    Microcontroller,
+    LowPassFilter,
+}
+
+/// What the protocol allows for a code and whether the unit keeps it
+/// across power cycles.
+#[derive(Debug, Clone, Copy)]
+pub struct CodeInfo {
+    pub code: &'static [u8],
+    pub name: &'static str,
+    pub category: Category,
+    // Can be read with "[CODE] G".
+    pub get: bool,
+    // Can be written with "[CODE] S".
+    pub set: bool,
+    // Stored in EEPROM.
+    pub persistent: bool,
+}
+
+impl CodeInfo {
+    const fn new(
+        code: &'static [u8],
+        name: &'static str,
+        category: Category,
+        get: bool,
+        set: bool,
+        persistent: bool,
+    ) -> Self {
+        Self {
+            code,
+            name,
+            category,
+            get,
+            set,
+            persistent,
+        }
+    }
+
+    pub fn code_str(&self) -> &'static str {
+        std::str::from_utf8(self.code).expect("Codes are ASCII.")
+    }
+
+    /// "S/G", "G", "S", or "-" for codes the unit only reports.
+    pub fn access(&self) -> &'static str {
+        match (self.set, self.get) {
+            (true, true) => "S/G",
+            (false, true) => "G",
+            (true, false) => "S",
+            (false, false) => "-",
+        }
+    }
+}
+
+impl fmt::Display for CodeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<4}  {:<15}  {:<3}  {:<8}  {}",
+            self.code_str(),
+            format!("{:?}", self.category),
+            self.access(),
+            if self.persistent {
+                "eeprom"
+            } else {
+                "volatile"
+            },
+            self.name
+        )
+    }
+}
+
+// Access follows the Set/Get column of the serial API document
+// (1012_WSPR-TX_Desktop_Serial_API_2.17.pdf), which is what the rest
+// of the crate means by "the API document". Codes it lists only as
+// status messages are reported by the unit, except MPS, which is sent
+// to start a pause, and MVC, which answers a poll. Options, Data and
+// Factory settings are kept in EEPROM, everything else is runtime
+// state.
+#[rustfmt::skip]
+pub const CODES: &[CodeInfo] = &[
+    CodeInfo::new(CurrentModeCommand::CODE, "Current mode", Category::Current, true, true, false),
+    CodeInfo::new(CurrentReferenceCommand::CODE, "Current reference", Category::Current, true, false, false),
+    CodeInfo::new(TxPauseOption::CODE, "TX pause", Category::Option, true, true, true),
+    CodeInfo::new(StartModeOption::CODE, "Start mode", Category::Option, true, true, true),
+    CodeInfo::new(BandTxEnable::CODE, "Band TX enable", Category::Option, true, true, true),
+    CodeInfo::new(LocationSourceOption::CODE, "Location source", Category::Option, true, true, true),
+    CodeInfo::new(LocatorPrecisionOption::CODE, "Locator precision", Category::Option, true, true, true),
+    CodeInfo::new(PowerEncodingOption::CODE, "Power encoding", Category::Option, true, true, true),
+    CodeInfo::new(TimeSlotOption::CODE, "Time slot", Category::Option, true, true, true),
+    CodeInfo::new(PrefixSuffixOption::CODE, "Prefix/suffix", Category::Option, true, true, true),
+    CodeInfo::new(ConstellationOption::CODE, "GPS constellations", Category::Option, true, true, true),
+    CodeInfo::new(CallSignData::CODE, "Call sign", Category::Data, true, true, true),
+    CodeInfo::new(SuffixData::CODE, "Suffix", Category::Data, true, true, true),
+    CodeInfo::new(PrefixData::CODE, "Prefix", Category::Data, true, true, true),
+    CodeInfo::new(Locator4Data::CODE, "Manual locator 4", Category::Data, true, true, true),
+    CodeInfo::new(Locator6Data::CODE, "Manual locator 6", Category::Data, true, true, true),
+    CodeInfo::new(PowerData::CODE, "Power", Category::Data, true, true, true),
+    CodeInfo::new(NameData::CODE, "Name", Category::Data, true, true, true),
+    CodeInfo::new(GeneratorFrequencyData::CODE, "Generator frequency", Category::Data, true, true, true),
+    CodeInfo::new(ExternalReferenceFrequencyData::CODE, "External reference frequency", Category::Data, true, true, true),
+    CodeInfo::new(ProductModelNumberFactory::CODE, "Product model", Category::Factory, true, false, true),
+    CodeInfo::new(HardwareVersionFactory::CODE, "Hardware version", Category::Factory, true, true, true),
+    CodeInfo::new(HardwareRevisionFactory::CODE, "Hardware revision", Category::Factory, true, true, true),
+    CodeInfo::new(SoftwareVersionFactory::CODE, "Software version", Category::Factory, true, false, true),
+    CodeInfo::new(SoftwareRevisionFactory::CODE, "Software revision", Category::Factory, true, false, true),
+    CodeInfo::new(ReferenceOscillatorFrequencyFactory::CODE, "Reference oscillator frequency", Category::Factory, true, true, true),
+    CodeInfo::new(LowPassFilterFactory::CODE, "Low pass filter fitted", Category::Factory, true, true, true),
+    CodeInfo::new(Locator4GPS::CODE, "GPS locator 4", Category::Gps, false, false, false),
+    CodeInfo::new(Locator6GPS::CODE, "GPS locator 6", Category::Gps, false, false, false),
+    CodeInfo::new(TimeGPS::CODE, "GPS time", Category::Gps, false, false, false),
+    CodeInfo::new(LockStatusGPS::CODE, "GPS lock", Category::Gps, false, false, false),
+    CodeInfo::new(SatelliteInfoGPS::CODE, "GPS satellite", Category::Gps, false, false, false),
+    CodeInfo::new(TransmitterFrequency::CODE, "TX frequency", Category::Transmitter, false, false, false),
+    CodeInfo::new(TransmitterStatus::CODE, "TX on", Category::Transmitter, false, false, false),
+    CodeInfo::new(TransmitterCurrentBand::CODE, "TX band", Category::Transmitter, false, false, false),
+    CodeInfo::new(TransmitterWSPRSymbol::CODE, "TX WSPR symbol", Category::Transmitter, false, false, false),
+    CodeInfo::new(TransmitterBandCycleComplete::CODE, "TX band cycle complete", Category::Transmitter, false, false, false),
+    CodeInfo::new(MicrocontrollerPause::CODE, "Microcontroller pause", Category::Microcontroller, false, true, false),
+    CodeInfo::new(MicrocontrollerInfo::CODE, "Microcontroller info", Category::Microcontroller, false, false, false),
+    CodeInfo::new(MicrocontrollerVoltage::CODE, "Microcontroller VCC", Category::Microcontroller, true, false, false),
+    CodeInfo::new(LowPassFilterSet::CODE, "Low pass filter in use", Category::LowPassFilter, false, false, false),
+];
+
+pub fn code_info(code: &[u8]) -> Option<&'static CodeInfo> {
+    CODES.iter().find(|info| info.code == code)
+}
+
+/// Refuse to set codes the protocol says are read only.
+pub fn ensure_settable(code: &[u8]) -> Result<()> {
+    match code_info(code) {
+        Some(info) if !info.set => bail!("{} ({}) can't be set", info.code_str(), info.name),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::{
+    Constellation, ConstellationOption, Event, GpsTracker, Response, SatelliteStats, ZachtekDevice,
+};
+
+// How often satellite counts are sampled during a trial.
+const SAMPLE_INTERVAL: TimeDelta = TimeDelta::seconds(10);
+
+// Longest wait for the unit to come back after switching.
+const REBOOT_DEADLINE: Duration = Duration::from_secs(30);
+
+impl Constellation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Constellation::GPSOnly => "gps",
+            Constellation::BeiDouOnly => "beidou",
+            Constellation::All => "all",
+        }
+    }
+}
+
+impl fmt::Display for Constellation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Constellation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.trim().to_ascii_lowercase().as_str() {
+            "gps" => Constellation::GPSOnly,
+            "beidou" => Constellation::BeiDouOnly,
+            "all" => Constellation::All,
+            _ => bail!("Unknown constellation '{s}', expected gps, beidou or all"),
+        })
+    }
+}
+
+/// How the GPS did with one constellation setting.
+#[derive(Debug, Clone)]
+pub struct ConstellationTrial {
+    pub constellation: Constellation,
+    pub duration: Duration,
+    // `None` if it never locked.
+    pub time_to_first_fix: Option<Duration>,
+    pub lock_losses: usize,
+    pub samples: Vec<SatelliteStats>,
+}
+
+impl ConstellationTrial {
+    pub fn mean_tracked(&self) -> f64 {
+        if self.samples.is_empty() {
+            return 0.;
+        }
+        self.samples
+            .iter()
+            .map(|sample| sample.tracked as f64)
+            .sum::<f64>()
+            / self.samples.len() as f64
+    }
+
+    pub fn max_tracked(&self) -> usize {
+        self.samples
+            .iter()
+            .map(|sample| sample.tracked)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn mean_snr(&self) -> Option<f64> {
+        let snrs: Vec<f64> = self
+            .samples
+            .iter()
+            .filter_map(|sample| sample.mean_snr)
+            .collect();
+        if snrs.is_empty() {
+            return None;
+        }
+        Some(snrs.iter().sum::<f64>() / snrs.len() as f64)
+    }
+
+    // Locking at all comes first, then holding lock, then satellites
+    // tracked, then signal strength.
+    fn rank(&self, other: &Self) -> std::cmp::Ordering {
+        self.time_to_first_fix
+            .is_some()
+            .cmp(&other.time_to_first_fix.is_some())
+            .then(other.lock_losses.cmp(&self.lock_losses))
+            .then(self.mean_tracked().total_cmp(&other.mean_tracked()))
+            .then(
+                self.mean_snr()
+                    .unwrap_or(0.)
+                    .total_cmp(&other.mean_snr().unwrap_or(0.)),
+            )
+    }
+}
+
+impl fmt::Display for ConstellationTrial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<6} ", self.constellation.name())?;
+        match self.time_to_first_fix {
+            Some(time_to_first_fix) => write!(f, "first fix {time_to_first_fix:?}")?,
+            None => write!(f, "no fix in {:?}", self.duration)?,
+        }
+        write!(
+            f,
+            ", {} lock losses, {:.1} satellites tracked (max {})",
+            self.lock_losses,
+            self.mean_tracked(),
+            self.max_tracked()
+        )?;
+        if let Some(snr) = self.mean_snr() {
+            write!(f, ", mean SNR {snr:.1} dB-Hz")?;
+        }
+        Ok(())
+    }
+}
+
+/// The trial that did best, if any locked.
+pub fn best_constellation(trials: &[ConstellationTrial]) -> Option<&ConstellationTrial> {
+    trials
+        .iter()
+        .filter(|trial| trial.time_to_first_fix.is_some())
+        .max_by(|a, b| a.rank(b))
+}
+
+impl ZachtekDevice {
+    pub fn read_constellation(&mut self) -> Result<Constellation> {
+        self.query(ConstellationOption::CODE, b"", |response| match response {
+            Response::ConstellationOption(option) => Some(option.constellation),
+            _ => None,
+        })
+    }
+
+    pub fn write_constellation(&mut self, constellation: Constellation) -> Result<()> {
+        self.warn_if_constellation_unsupported(constellation);
+        self.set_verified(ConstellationOption::CODE, &[constellation.into()])
+    }
+
+    /// Try each constellation the unit supports for `duration`,
+    /// resetting the unit after each switch and timing the first fix
+    /// from its restart. Whether the GPS keeps its fix or almanac
+    /// across the reset isn't documented, so later trials may start
+    /// warm. The setting in use before is restored afterwards.
+    pub fn compare_constellations(
+        &mut self,
+        duration: Duration,
+    ) -> Result<Vec<ConstellationTrial>> {
+        let original = self.read_constellation()?;
+        let mut trials = Vec::new();
+        let result = self
+            .capabilities()
+            .constellations
+            .iter()
+            .try_for_each(|constellation| {
+                trials.push(self.constellation_trial(*constellation, duration)?);
+                Ok(())
+            });
+        info!("Restoring {original}");
+        self.write_constellation(original)?;
+        self.reboot(REBOOT_DEADLINE)?;
+        result.map(|()| trials)
+    }
+
+    fn constellation_trial(
+        &mut self,
+        constellation: Constellation,
+        duration: Duration,
+    ) -> Result<ConstellationTrial> {
+        info!("Trying {constellation} for {duration:?}");
+        self.write_constellation(constellation)?;
+        let mut gps = GpsTracker::new();
+        let report = self.reboot(REBOOT_DEADLINE)?;
+        gps.reset(report.reset_time);
+        for (time, response) in report.replay() {
+            gps.check(response, *time);
+        }
+        let mut samples = Vec::new();
+        let mut next_sample: DateTime<Utc> = Utc::now() + SAMPLE_INTERVAL;
+        let started = Instant::now();
+        while started.elapsed() < duration {
+            if let Some(Event::Response(response)) = self.read_event()? {
+                gps.check(&response, Utc::now());
+            }
+            let now = Utc::now();
+            if now >= next_sample {
+                samples.push(gps.satellite_stats(now));
+                next_sample = now + SAMPLE_INTERVAL;
+            }
+        }
+        let trial = ConstellationTrial {
+            constellation,
+            duration,
+            time_to_first_fix: gps.time_to_first_fix,
+            lock_losses: gps.lock_losses.len(),
+            samples,
+        };
+        info!("{trial}");
+        Ok(trial)
+    }
+}
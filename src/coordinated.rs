@@ -0,0 +1,139 @@
+use chrono::{DateTime, Duration as ChronoDuration, Timelike, Utc};
+use tracing::warn;
+
+use crate::{Band, Response};
+
+// The WSPR band-coordinated hopping table: every station on the
+// schedule is on the same band in each 2 minute slot, cycling through
+// ten bands every 20 minutes starting on the hour. The third slot is
+// 60m, which these units cannot transmit on.
+pub const COORDINATED_BANDS: [Option<Band>; 10] = [
+    Some(Band::B160m),
+    Some(Band::B80m),
+    None,
+    Some(Band::B40m),
+    Some(Band::B30m),
+    Some(Band::B20m),
+    Some(Band::B17m),
+    Some(Band::B15m),
+    Some(Band::B12m),
+    Some(Band::B10m),
+];
+
+// The unit may announce the band (TBN) shortly before the slot starts.
+const ANNOUNCE_TOLERANCE: ChronoDuration = ChronoDuration::seconds(10);
+
+/// Band the coordinated schedule has active at `time`, or `None` in
+/// the 60m slot.
+pub fn coordinated_band(time: DateTime<Utc>) -> Option<Band> {
+    COORDINATED_BANDS[(time.minute() % 20 / 2) as usize]
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoppingMismatch {
+    pub time: DateTime<Utc>,
+    pub expected: Option<Band>,
+    pub observed: Band,
+}
+
+/// Cross-checks a unit's reported bands (TBN) against the coordinated
+/// hopping table.
+#[derive(Debug, Clone, Default)]
+pub struct HoppingChecker {
+    pub observed: usize,
+    pub mismatches: Vec<HoppingMismatch>,
+}
+
+impl HoppingChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a response received at `time`. Returns the mismatch, if
+    /// this response is a band report that disagrees with the table.
+    pub fn check(&mut self, response: &Response, time: DateTime<Utc>) -> Option<HoppingMismatch> {
+        let Response::TransmitterCurrentBand(current) = response else {
+            return None;
+        };
+        self.observed += 1;
+        let expected = coordinated_band(time);
+        if expected == Some(current.band)
+            || coordinated_band(time + ANNOUNCE_TOLERANCE) == Some(current.band)
+        {
+            return None;
+        }
+        let mismatch = HoppingMismatch {
+            time,
+            expected,
+            observed: current.band,
+        };
+        warn!(
+            "Band hopping disagrees with coordinated schedule at {}: expected {:?}, observed {:?}",
+            time, expected, current.band
+        );
+        self.mismatches.push(mismatch.clone());
+        Some(mismatch)
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn band_report(band: Band) -> Response {
+        Response::TransmitterCurrentBand(crate::TransmitterCurrentBand { band })
+    }
+
+    fn at(minute: u32, second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, minute, second).unwrap()
+    }
+
+    #[test]
+    fn table_repeats_every_twenty_minutes() {
+        assert_eq!(coordinated_band(at(0, 0)), Some(Band::B160m));
+        assert_eq!(coordinated_band(at(3, 59)), Some(Band::B80m));
+        assert_eq!(coordinated_band(at(4, 0)), None);
+        assert_eq!(coordinated_band(at(38, 0)), Some(Band::B10m));
+    }
+
+    #[test]
+    fn matching_bands_are_consistent() {
+        let mut checker = HoppingChecker::new();
+        assert_eq!(checker.check(&band_report(Band::B160m), at(0, 30)), None);
+        assert_eq!(checker.check(&band_report(Band::B40m), at(6, 0)), None);
+        assert_eq!(checker.observed, 2);
+        assert!(checker.is_consistent());
+    }
+
+    #[test]
+    fn early_announcement_is_tolerated() {
+        let mut checker = HoppingChecker::new();
+        assert_eq!(checker.check(&band_report(Band::B80m), at(1, 55)), None);
+        assert!(checker.check(&band_report(Band::B80m), at(1, 40)).is_some());
+    }
+
+    #[test]
+    fn mismatch_is_recorded() {
+        let mut checker = HoppingChecker::new();
+        let mismatch = checker.check(&band_report(Band::B20m), at(4, 30)).unwrap();
+        assert_eq!(mismatch.expected, None);
+        assert_eq!(mismatch.observed, Band::B20m);
+        assert_eq!(checker.mismatches, vec![mismatch]);
+        assert!(!checker.is_consistent());
+    }
+
+    #[test]
+    fn other_responses_are_ignored() {
+        let mut checker = HoppingChecker::new();
+        let response = Response::Locator4Data(crate::Locator4Data {
+            locator_4: "JO65".into(),
+        });
+        assert_eq!(checker.check(&response, at(4, 30)), None);
+        assert_eq!(checker.observed, 0);
+    }
+}
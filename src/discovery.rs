@@ -0,0 +1,64 @@
+use anyhow::Result;
+use serialport::{SerialPortInfo, SerialPortType, UsbPortInfo};
+use std::fmt;
+use std::time::Duration;
+use tracing::debug;
+
+use crate::{open_port, DeviceInfo, ZachtekDevice};
+
+/// A unit found by `discover`.
+#[derive(Debug, Clone)]
+pub struct DiscoveredDevice {
+    pub port_name: String,
+    pub usb: Option<UsbPortInfo>,
+    pub info: DeviceInfo,
+    pub call_sign: String,
+}
+
+impl DiscoveredDevice {
+    pub fn serial_number(&self) -> Option<&str> {
+        self.usb.as_ref()?.serial_number.as_deref()
+    }
+}
+
+impl fmt::Display for DiscoveredDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} {}", self.port_name, self.info, self.call_sign)?;
+        if let Some(serial_number) = self.serial_number() {
+            write!(f, " (serial {serial_number})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Probe every USB serial port for a unit. Ports that can't be opened
+/// or don't answer a product model query are skipped.
+pub fn discover(timeout: Duration) -> Result<Vec<DiscoveredDevice>> {
+    let mut found = vec![];
+    for port_info in serialport::available_ports()? {
+        let SerialPortType::UsbPort(ref usb) = port_info.port_type else {
+            debug!("Skipping non-USB port {}", port_info.port_name);
+            continue;
+        };
+        match probe(&port_info, timeout) {
+            Ok((info, call_sign)) => found.push(DiscoveredDevice {
+                port_name: port_info.port_name.clone(),
+                usb: Some(usb.clone()),
+                info,
+                call_sign,
+            }),
+            Err(err) => debug!("No unit on {}: {err:#}", port_info.port_name),
+        }
+    }
+    Ok(found)
+}
+
+fn probe(port_info: &SerialPortInfo, timeout: Duration) -> Result<(DeviceInfo, String)> {
+    let port = open_port(&port_info.port_name, timeout)?;
+    let mut device = ZachtekDevice::new(port);
+    device.set_run()?;
+    device.clear_input()?;
+    let info = device.handshake()?;
+    let call_sign = device.read_call_sign()?;
+    Ok((info, call_sign))
+}
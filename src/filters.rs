@@ -0,0 +1,242 @@
+use anyhow::Result;
+use std::fmt;
+
+use crate::{Band, BandSet, FilterBank, LowPassFilterFactory, ZachtekDevice};
+
+// A band's low pass filter is taken to pass everything up to this
+// factor above the band's WSPR frequency.
+const FILTER_CUTOFF_RATIO: f64 = 1.2;
+
+// Harmonics worth worrying about.
+const HARMONICS: std::ops::RangeInclusive<u8> = 2..=5;
+
+/// Filter the firmware would switch in for a band.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterSelection {
+    // A filter for this band.
+    Exact(FilterBank),
+    // The filter for the nearest higher band.
+    Higher(FilterBank, Band),
+    // No filter is a good match; the bank with a plain link is used.
+    Link(FilterBank),
+    // Nothing usable is fitted.
+    None,
+}
+
+impl FilterSelection {
+    pub fn select(band: Band, filters: &[LowPassFilterFactory]) -> Self {
+        if let Some(filter) = filters.iter().find(|filter| filter.band == band) {
+            return FilterSelection::Exact(filter.filter_bank);
+        }
+        let frequency = band.wspr_frequency();
+        let higher = filters
+            .iter()
+            .filter_map(|filter| Some((filter, filter.band.wspr_frequency()?)))
+            .filter(|(_, cutoff)| Some(*cutoff) > frequency)
+            .min_by_key(|(_, cutoff)| *cutoff);
+        if let Some((filter, _)) = higher {
+            return FilterSelection::Higher(filter.filter_bank, filter.band);
+        }
+        match filters.iter().find(|filter| filter.band == Band::NoFilter) {
+            Some(filter) => FilterSelection::Link(filter.filter_bank),
+            None => FilterSelection::None,
+        }
+    }
+}
+
+impl fmt::Display for FilterSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterSelection::Exact(bank) => write!(f, "bank {bank:?}"),
+            FilterSelection::Higher(bank, band) => write!(f, "bank {bank:?} ({band} filter)"),
+            FilterSelection::Link(bank) => write!(f, "bank {bank:?} (link, unfiltered)"),
+            FilterSelection::None => write!(f, "no filter"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BandFilterCheck {
+    pub band: Band,
+    pub selection: FilterSelection,
+    // Harmonic numbers that fall below the selected filter's cutoff.
+    pub unfiltered_harmonics: Vec<u8>,
+}
+
+impl BandFilterCheck {
+    pub fn new(band: Band, filters: &[LowPassFilterFactory]) -> Self {
+        let selection = FilterSelection::select(band, filters);
+        let cutoff = match selection {
+            FilterSelection::Exact(_) => band.wspr_frequency(),
+            FilterSelection::Higher(_, filter_band) => filter_band.wspr_frequency(),
+            FilterSelection::Link(_) | FilterSelection::None => None,
+        };
+        let frequency = band.wspr_frequency().unwrap_or_default() as f64;
+        let unfiltered_harmonics = HARMONICS
+            .filter(|harmonic| match cutoff {
+                Some(cutoff) => {
+                    frequency * (*harmonic as f64) < cutoff as f64 * FILTER_CUTOFF_RATIO
+                }
+                None => true,
+            })
+            .collect();
+        Self {
+            band,
+            selection,
+            unfiltered_harmonics,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        matches!(
+            self.selection,
+            FilterSelection::Exact(_) | FilterSelection::Higher(..)
+        ) && self.unfiltered_harmonics.is_empty()
+    }
+}
+
+/// Checks that every enabled band has a low pass filter that will
+/// suppress its harmonics.
+#[derive(Debug, Clone)]
+pub struct FilterCheck {
+    pub filters: Vec<LowPassFilterFactory>,
+    pub enabled: BandSet,
+    pub bands: Vec<BandFilterCheck>,
+}
+
+impl FilterCheck {
+    pub fn new(filters: Vec<LowPassFilterFactory>, enabled: BandSet) -> Self {
+        let bands = enabled
+            .iter()
+            .map(|band| BandFilterCheck::new(band, &filters))
+            .collect();
+        Self {
+            filters,
+            enabled,
+            bands,
+        }
+    }
+
+    /// Enabled bands with nothing usable fitted.
+    pub fn unfiltered(&self) -> BandSet {
+        self.with_selection(|selection| matches!(selection, FilterSelection::None))
+    }
+
+    /// Enabled bands that would fall back to the link bank.
+    pub fn link_fallback(&self) -> BandSet {
+        self.with_selection(|selection| matches!(selection, FilterSelection::Link(_)))
+    }
+
+    /// Enabled bands with at least one harmonic passing their filter.
+    pub fn harmonic_problems(&self) -> impl Iterator<Item = &BandFilterCheck> {
+        self.bands
+            .iter()
+            .filter(|check| !check.unfiltered_harmonics.is_empty())
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.bands.iter().all(BandFilterCheck::is_ok)
+    }
+
+    fn with_selection(&self, predicate: impl Fn(&FilterSelection) -> bool) -> BandSet {
+        self.bands
+            .iter()
+            .filter(|check| predicate(&check.selection))
+            .map(|check| check.band)
+            .collect()
+    }
+}
+
+impl fmt::Display for FilterCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for filter in &self.filters {
+            writeln!(f, "bank {:?}: {}", filter.filter_bank, filter.band)?;
+        }
+        for check in &self.bands {
+            write!(f, "{:>5}: {}", check.band.name(), check.selection)?;
+            if !check.unfiltered_harmonics.is_empty() {
+                write!(f, ", unfiltered harmonics {:?}", check.unfiltered_harmonics)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl ZachtekDevice {
+    pub fn check_filters(&mut self) -> Result<FilterCheck> {
+        let filters = self.read_filters()?;
+        let enabled = self.read_band_set()?;
+        Ok(FilterCheck::new(filters, enabled))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(filter_bank: FilterBank, band: Band) -> LowPassFilterFactory {
+        LowPassFilterFactory { filter_bank, band }
+    }
+
+    #[test]
+    fn selection_prefers_exact_then_higher_then_link() {
+        let filters = [
+            filter(FilterBank::A, Band::B20m),
+            filter(FilterBank::B, Band::B10m),
+            filter(FilterBank::C, Band::NoFilter),
+        ];
+        assert_eq!(
+            FilterSelection::select(Band::B20m, &filters),
+            FilterSelection::Exact(FilterBank::A)
+        );
+        assert_eq!(
+            FilterSelection::select(Band::B40m, &filters),
+            FilterSelection::Higher(FilterBank::A, Band::B20m)
+        );
+        assert_eq!(
+            FilterSelection::select(Band::B15m, &filters),
+            FilterSelection::Higher(FilterBank::B, Band::B10m)
+        );
+        assert_eq!(
+            FilterSelection::select(Band::B6m, &filters),
+            FilterSelection::Link(FilterBank::C)
+        );
+        assert_eq!(
+            FilterSelection::select(Band::B6m, &filters[..2]),
+            FilterSelection::None
+        );
+    }
+
+    #[test]
+    fn harmonics_below_the_cutoff_are_reported() {
+        let filters = [filter(FilterBank::A, Band::B20m)];
+        let exact = BandFilterCheck::new(Band::B20m, &filters);
+        assert!(exact.unfiltered_harmonics.is_empty());
+        assert!(exact.is_ok());
+        // 40m's second harmonic is just above 20m, well under its cutoff.
+        let higher = BandFilterCheck::new(Band::B40m, &filters);
+        assert_eq!(higher.unfiltered_harmonics, [2]);
+        assert!(!higher.is_ok());
+        let unfiltered = BandFilterCheck::new(Band::B6m, &[]);
+        assert_eq!(unfiltered.unfiltered_harmonics, [2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn check_groups_enabled_bands_by_problem() {
+        let filters = vec![
+            filter(FilterBank::A, Band::B20m),
+            filter(FilterBank::B, Band::NoFilter),
+        ];
+        let enabled: BandSet = [Band::B20m, Band::B40m, Band::B6m].into_iter().collect();
+        let check = FilterCheck::new(filters.clone(), enabled);
+        assert!(!check.is_ok());
+        assert_eq!(check.link_fallback(), [Band::B6m].into_iter().collect());
+        assert!(check.unfiltered().iter().next().is_none());
+        let problems: Vec<Band> = check.harmonic_problems().map(|check| check.band).collect();
+        assert_eq!(problems, [Band::B40m, Band::B6m]);
+
+        let check = FilterCheck::new(filters, [Band::B20m].into_iter().collect());
+        assert!(check.is_ok());
+    }
+}
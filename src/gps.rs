@@ -0,0 +1,335 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::{GpsLock, Response, SatelliteInfoGPS};
+
+// The GPS reports every satellite in view about once a second; one
+// not reported for this long has set or been lost.
+const SATELLITE_TIMEOUT: TimeDelta = TimeDelta::seconds(30);
+
+/// Time from losing lock (GLC F) to regaining it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockLoss {
+    pub start: DateTime<Utc>,
+    // `None` while still unlocked.
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl LockLoss {
+    /// How long lock was lost for, up to `now` if not regained yet.
+    pub fn duration(&self, now: DateTime<Utc>) -> Duration {
+        (self.end.unwrap_or(now) - self.start)
+            .to_std()
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocatorChange {
+    pub time: DateTime<Utc>,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GpsEvent {
+    // First lock since the unit started.
+    FirstFix(Duration),
+    LockLost(DateTime<Utc>),
+    LockRegained(LockLoss),
+    LocatorChanged(LocatorChange),
+}
+
+impl fmt::Display for GpsEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GpsEvent::FirstFix(time_to_first_fix) => {
+                write!(f, "GPS locked {time_to_first_fix:?} after start")
+            }
+            GpsEvent::LockLost(time) => write!(f, "{time}: GPS lost lock"),
+            GpsEvent::LockRegained(loss) => {
+                let end = loss.end.unwrap_or(loss.start);
+                write!(f, "{end}: GPS locked again after {:?}", loss.duration(end))
+            }
+            GpsEvent::LocatorChanged(change) => write!(
+                f,
+                "{}: GPS locator {} -> {}",
+                change.time, change.from, change.to
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SatelliteStats {
+    // Satellites reported recently.
+    pub in_view: usize,
+    // Of those, the ones with an SNR.
+    pub tracked: usize,
+    // Mean SNR of the tracked satellites (dB-Hz).
+    pub mean_snr: Option<f64>,
+}
+
+impl fmt::Display for SatelliteStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in view, {} tracked", self.in_view, self.tracked)?;
+        if let Some(snr) = self.mean_snr {
+            write!(f, ", mean SNR {snr:.1} dB-Hz")?;
+        }
+        Ok(())
+    }
+}
+
+/// Follows GPS lock (GLC), satellites (GSI) and locator (GL4/GL6) to
+/// show how well the antenna sees the sky. Time to first fix is
+/// measured from the unit starting (MIN) or from `reset`.
+#[derive(Debug, Clone, Default)]
+pub struct GpsTracker {
+    pub locked: Option<bool>,
+    pub reset_time: Option<DateTime<Utc>>,
+    pub time_to_first_fix: Option<Duration>,
+    pub lock_losses: Vec<LockLoss>,
+    pub locator_4: Option<String>,
+    pub locator_6: Option<String>,
+    pub locator_changes: Vec<LocatorChange>,
+    satellites: HashMap<u8, (DateTime<Utc>, SatelliteInfoGPS)>,
+}
+
+impl GpsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The unit was reset at `time`; the next lock is its first fix.
+    pub fn reset(&mut self, time: DateTime<Utc>) {
+        // A loss still open ends with the reset; the unit starts again
+        // from no fix.
+        if let Some(loss) = self
+            .lock_losses
+            .last_mut()
+            .filter(|loss| loss.end.is_none())
+        {
+            loss.end = Some(time);
+        }
+        self.reset_time = Some(time);
+        self.time_to_first_fix = None;
+        self.locked = None;
+        self.satellites.clear();
+    }
+
+    /// Feed a response received at `time`. Returns the event, if this
+    /// response changes the lock or locator.
+    pub fn check(&mut self, response: &Response, time: DateTime<Utc>) -> Option<GpsEvent> {
+        match response {
+            Response::MicrocontrollerInfo(_) => {
+                self.reset(time);
+                None
+            }
+            Response::LockStatusGPS(status) => {
+                self.update_lock(matches!(status.lock, GpsLock::Locked), time)
+            }
+            Response::SatelliteInfoGPS(satellite) => {
+                self.satellites
+                    .insert(satellite.id, (time, satellite.clone()));
+                None
+            }
+            Response::Locator4GPS(locator) => {
+                let to = locator.maidenhead_4.trim().to_string();
+                let change = Self::update_locator(&mut self.locator_4, to, time)?;
+                // A move shows up in both; only count it once.
+                if self.locator_6.is_some() {
+                    return None;
+                }
+                Some(self.push_locator_change(change))
+            }
+            Response::Locator6GPS(locator) => {
+                let to = locator.maidenhead_6.trim().to_string();
+                Self::update_locator(&mut self.locator_6, to, time)
+                    .map(|change| self.push_locator_change(change))
+            }
+            _ => None,
+        }
+    }
+
+    fn update_lock(&mut self, locked: bool, time: DateTime<Utc>) -> Option<GpsEvent> {
+        let was_locked = self.locked.replace(locked);
+        if was_locked == Some(locked) {
+            return None;
+        }
+        if locked {
+            if let Some(loss) = self
+                .lock_losses
+                .last_mut()
+                .filter(|loss| loss.end.is_none())
+            {
+                loss.end = Some(time);
+                let event = GpsEvent::LockRegained(*loss);
+                info!("{event}");
+                return Some(event);
+            }
+            if self.time_to_first_fix.is_none() {
+                let reset_time = self.reset_time?;
+                let time_to_first_fix = (time - reset_time).to_std().unwrap_or_default();
+                self.time_to_first_fix = Some(time_to_first_fix);
+                let event = GpsEvent::FirstFix(time_to_first_fix);
+                info!("{event}");
+                return Some(event);
+            }
+            None
+        } else if was_locked == Some(true) {
+            self.lock_losses.push(LockLoss {
+                start: time,
+                end: None,
+            });
+            let event = GpsEvent::LockLost(time);
+            warn!("{event}");
+            Some(event)
+        } else {
+            None
+        }
+    }
+
+    // The unit reports an empty locator until it has a fix.
+    fn update_locator(
+        current: &mut Option<String>,
+        to: String,
+        time: DateTime<Utc>,
+    ) -> Option<LocatorChange> {
+        if to.is_empty() {
+            return None;
+        }
+        let from = current.replace(to.clone())?;
+        if from == to {
+            return None;
+        }
+        Some(LocatorChange { time, from, to })
+    }
+
+    fn push_locator_change(&mut self, change: LocatorChange) -> GpsEvent {
+        warn!("GPS locator changed from {} to {}", change.from, change.to);
+        self.locator_changes.push(change.clone());
+        GpsEvent::LocatorChanged(change)
+    }
+
+    /// The most precise locator reported.
+    pub fn locator(&self) -> Option<&str> {
+        self.locator_6.as_deref().or(self.locator_4.as_deref())
+    }
+
+    /// Satellites reported in the last 30 seconds before `now`.
+    pub fn satellites(&self, now: DateTime<Utc>) -> impl Iterator<Item = &SatelliteInfoGPS> {
+        self.satellites
+            .values()
+            .filter(move |(time, _)| now - *time <= SATELLITE_TIMEOUT)
+            .map(|(_, satellite)| satellite)
+    }
+
+    pub fn satellite_stats(&self, now: DateTime<Utc>) -> SatelliteStats {
+        let snrs: Vec<u8> = self
+            .satellites(now)
+            .filter_map(|satellite| satellite.snr)
+            .collect();
+        SatelliteStats {
+            in_view: self.satellites(now).count(),
+            tracked: snrs.len(),
+            mean_snr: (!snrs.is_empty())
+                .then(|| snrs.iter().map(|snr| *snr as f64).sum::<f64>() / snrs.len() as f64),
+        }
+    }
+
+    /// Total time without lock since the first fix, up to `now`.
+    pub fn unlocked_time(&self, now: DateTime<Utc>) -> Duration {
+        self.lock_losses.iter().map(|loss| loss.duration(now)).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process_line;
+    use chrono::TimeZone;
+
+    fn response(line: &str) -> Response {
+        process_line(line.as_bytes().to_vec()).unwrap()
+    }
+
+    fn at(second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, second).unwrap()
+    }
+
+    #[test]
+    fn first_fix_is_timed_from_reset() {
+        let mut gps = GpsTracker::new();
+        gps.reset(at(0));
+        assert_eq!(gps.check(&response("{GLC} F"), at(5)), None);
+        assert_eq!(
+            gps.check(&response("{GLC} T"), at(40)),
+            Some(GpsEvent::FirstFix(Duration::from_secs(40)))
+        );
+        assert_eq!(gps.time_to_first_fix, Some(Duration::from_secs(40)));
+    }
+
+    #[test]
+    fn startup_message_restarts_the_clock() {
+        let mut gps = GpsTracker::new();
+        gps.reset(at(0));
+        gps.check(&response("{MIN} WSPR-TX Desktop"), at(3));
+        gps.check(&response("{GLC} T"), at(40));
+        assert_eq!(gps.time_to_first_fix, Some(Duration::from_secs(37)));
+    }
+
+    #[test]
+    fn no_first_fix_without_a_start() {
+        let mut gps = GpsTracker::new();
+        assert_eq!(gps.check(&response("{GLC} T"), at(1)), None);
+        assert_eq!(gps.time_to_first_fix, None);
+    }
+
+    #[test]
+    fn lock_losses_are_timed() {
+        let mut gps = GpsTracker::new();
+        gps.check(&response("{GLC} T"), at(0));
+        assert_eq!(
+            gps.check(&response("{GLC} F"), at(10)),
+            Some(GpsEvent::LockLost(at(10)))
+        );
+        assert_eq!(gps.unlocked_time(at(15)), Duration::from_secs(5));
+        let Some(GpsEvent::LockRegained(loss)) = gps.check(&response("{GLC} T"), at(30)) else {
+            panic!("lock not regained");
+        };
+        assert_eq!(loss.duration(at(59)), Duration::from_secs(20));
+        assert_eq!(gps.unlocked_time(at(59)), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn locator_moves_are_counted_once() {
+        let mut gps = GpsTracker::new();
+        gps.check(&response("{GL4} "), at(0));
+        gps.check(&response("{GL4} JO65"), at(1));
+        gps.check(&response("{GL6} JO65ab"), at(1));
+        assert_eq!(gps.check(&response("{GL4} JO66"), at(2)), None);
+        let Some(GpsEvent::LocatorChanged(change)) = gps.check(&response("{GL6} JO66ab"), at(2))
+        else {
+            panic!("locator change not reported");
+        };
+        assert_eq!(change.from, "JO65ab");
+        assert_eq!(gps.locator_changes.len(), 1);
+        assert_eq!(gps.locator(), Some("JO66ab"));
+    }
+
+    #[test]
+    fn satellites_time_out() {
+        let mut gps = GpsTracker::new();
+        gps.check(&response("{GSI} 05 120 45 38"), at(0));
+        gps.check(&response("{GSI} 07 300 10 30"), at(20));
+        gps.check(&response("{GSI} 12 010 05"), at(20));
+        let stats = gps.satellite_stats(at(20));
+        assert_eq!(stats.in_view, 3);
+        assert_eq!(stats.tracked, 2);
+        assert_eq!(stats.mean_snr, Some(34.));
+        assert_eq!(gps.satellite_stats(at(45)).in_view, 2);
+    }
+}
@@ -0,0 +1,153 @@
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::time::Duration;
+
+use crate::{
+    GpsTracker, LocatorMonitor, Reference, ReferenceMonitor, Response, SatelliteStats,
+    TxPauseTracker, VoltageMonitor, VoltageState, VoltageStats,
+};
+
+/// Snapshot of a unit's state for reporting.
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    pub time: DateTime<Utc>,
+    pub reference: Option<Reference>,
+    pub external_reference_hertz: Option<u32>,
+    pub reference_changes: usize,
+    pub reference_fallbacks: usize,
+    pub voltage_millivolts: Option<u32>,
+    pub voltage_state: VoltageState,
+    pub voltage_stats: Option<VoltageStats>,
+    pub tx_pause: Option<Duration>,
+    pub mean_cycle_gap: Option<Duration>,
+    pub tx_pause_mismatches: usize,
+    pub gps_locked: Option<bool>,
+    pub time_to_first_fix: Option<Duration>,
+    pub lock_losses: usize,
+    pub unlocked_time: Duration,
+    pub satellites: SatelliteStats,
+    pub locator: Option<String>,
+    pub locator_changes: usize,
+    pub manual_locator: Option<String>,
+    // From the GPS locator, if both are known.
+    pub manual_locator_distance_km: Option<f64>,
+    pub locator_mismatches: usize,
+}
+
+impl fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Health at {}", self.time)?;
+        match self.reference {
+            Some(reference) => writeln!(f, "  Reference: {reference:?}")?,
+            None => writeln!(f, "  Reference: unknown")?,
+        }
+        match self.external_reference_hertz {
+            Some(hertz) => writeln!(f, "  External reference: {hertz} Hz")?,
+            None => writeln!(f, "  External reference: unknown")?,
+        }
+        writeln!(
+            f,
+            "  Reference changes: {} ({} fallbacks)",
+            self.reference_changes, self.reference_fallbacks
+        )?;
+        match self.voltage_millivolts {
+            Some(millivolts) => writeln!(f, "  VCC: {millivolts} mV ({:?})", self.voltage_state)?,
+            None => writeln!(f, "  VCC: unknown")?,
+        }
+        if let Some(stats) = &self.voltage_stats {
+            writeln!(f, "  VCC history: {stats}")?;
+        }
+        match self.tx_pause {
+            Some(tx_pause) => writeln!(f, "  TX pause: {tx_pause:?}")?,
+            None => writeln!(f, "  TX pause: unknown")?,
+        }
+        if let Some(gap) = self.mean_cycle_gap {
+            writeln!(f, "  Mean gap after band cycle: {gap:?}")?;
+        }
+        writeln!(f, "  TX pause mismatches: {}", self.tx_pause_mismatches)?;
+        match self.gps_locked {
+            Some(true) => writeln!(f, "  GPS: locked")?,
+            Some(false) => writeln!(f, "  GPS: unlocked")?,
+            None => writeln!(f, "  GPS: unknown")?,
+        }
+        if let Some(time_to_first_fix) = self.time_to_first_fix {
+            writeln!(f, "  Time to first fix: {time_to_first_fix:?}")?;
+        }
+        writeln!(
+            f,
+            "  GPS lock losses: {} ({:?} unlocked)",
+            self.lock_losses, self.unlocked_time
+        )?;
+        writeln!(f, "  Satellites: {}", self.satellites)?;
+        match &self.locator {
+            Some(locator) => writeln!(f, "  GPS locator: {locator}")?,
+            None => writeln!(f, "  GPS locator: unknown")?,
+        }
+        writeln!(f, "  GPS locator changes: {}", self.locator_changes)?;
+        match (&self.manual_locator, self.manual_locator_distance_km) {
+            (Some(locator), Some(distance)) => {
+                writeln!(f, "  Manual locator: {locator} ({distance:.0} km from GPS)")?
+            }
+            (Some(locator), None) => writeln!(f, "  Manual locator: {locator}")?,
+            (None, _) => writeln!(f, "  Manual locator: unknown")?,
+        }
+        writeln!(f, "  Locator mismatches: {}", self.locator_mismatches)?;
+        Ok(())
+    }
+}
+
+/// Collects what the unit reports into a `HealthReport`.
+#[derive(Debug, Clone, Default)]
+pub struct HealthMonitor {
+    pub reference: ReferenceMonitor,
+    pub voltage: VoltageMonitor,
+    pub tx_pause: TxPauseTracker,
+    pub gps: GpsTracker,
+    pub locator: LocatorMonitor,
+}
+
+impl HealthMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a response received at `time`.
+    pub fn update(&mut self, response: &Response, time: DateTime<Utc>) {
+        self.reference.check(response, time);
+        self.voltage.check(response, time);
+        self.tx_pause.check(response, time);
+        self.gps.check(response, time);
+        self.locator.check(response, time);
+    }
+
+    pub fn report(&self, time: DateTime<Utc>) -> HealthReport {
+        HealthReport {
+            time,
+            reference: self.reference.current,
+            external_reference_hertz: self.reference.external_hertz,
+            reference_changes: self
+                .reference
+                .changes
+                .iter()
+                .filter(|change| change.from.is_some())
+                .count(),
+            reference_fallbacks: self.reference.fallbacks().count(),
+            voltage_millivolts: self.voltage.latest().map(|sample| sample.millivolts),
+            voltage_state: self.voltage.state,
+            voltage_stats: self.voltage.stats(),
+            tx_pause: self.tx_pause.tx_pause,
+            mean_cycle_gap: self.tx_pause.mean_cycle_gap(),
+            tx_pause_mismatches: self.tx_pause.mismatches.len(),
+            gps_locked: self.gps.locked,
+            time_to_first_fix: self.gps.time_to_first_fix,
+            lock_losses: self.gps.lock_losses.len(),
+            unlocked_time: self.gps.unlocked_time(time),
+            satellites: self.gps.satellite_stats(time),
+            locator: self.gps.locator().map(str::to_string),
+            locator_changes: self.gps.locator_changes.len(),
+            manual_locator: self.locator.manual().map(str::to_string),
+            manual_locator_distance_km: self.locator.distance_km(),
+            locator_mismatches: self.locator.mismatches.len(),
+        }
+    }
+}
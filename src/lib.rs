@@ -1,13 +1,73 @@
 use anyhow::{bail, ensure, Context, Result};
 use ascii::AsciiStr;
-use num_enum::{IntoPrimitive, TryFromPrimitive};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use num_enum::{FromPrimitive, IntoPrimitive, TryFromPrimitive};
+use serde::{Deserialize, Serialize};
 use serialport::{ClearBuffer, SerialPort};
 use std::io;
 use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
 use std::time::Duration;
-use tracing::{error, trace, warn};
-
-#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
+use tracing::{debug, error, trace, warn};
+
+mod analysis;
+mod bands;
+mod boot;
+mod calibration;
+mod codes;
+mod constellation;
+mod coordinated;
+mod discovery;
+mod filters;
+mod gps;
+mod health;
+mod location;
+mod maidenhead;
+mod manager;
+mod model;
+mod pause;
+mod power;
+mod reconnect;
+mod reference;
+mod schedule;
+mod spots;
+mod track;
+mod transmissions;
+mod tx_pause;
+mod verify;
+mod version;
+mod voltage;
+
+pub use analysis::*;
+pub use bands::*;
+pub use boot::*;
+pub use calibration::*;
+pub use codes::*;
+pub use constellation::*;
+pub use coordinated::*;
+pub use discovery::*;
+pub use filters::*;
+pub use gps::*;
+pub use health::*;
+pub use location::*;
+pub use maidenhead::*;
+pub use manager::*;
+pub use model::*;
+pub use power::*;
+pub use reconnect::*;
+pub use reference::*;
+pub use schedule::*;
+pub use spots::*;
+pub use track::*;
+pub use transmissions::*;
+pub use tx_pause::*;
+pub use verify::*;
+pub use version::*;
+pub use voltage::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum Mode {
     Sig = b'S',
@@ -15,7 +75,9 @@ pub enum Mode {
     Idle = b'N',
 }
 
-#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive, Serialize, Deserialize,
+)]
 #[repr(u8)]
 pub enum FilterBank {
     A = b'A',
@@ -24,7 +86,11 @@ pub enum FilterBank {
     D = b'D',
 }
 
-#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
+impl FilterBank {
+    pub const ALL: [FilterBank; 4] = [FilterBank::A, FilterBank::B, FilterBank::C, FilterBank::D];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum Reference {
     External = b'E',
@@ -52,15 +118,46 @@ pub enum PowerEncoding {
     Altitude = b'A',
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TimeSlot {
-    TenMinute,
-    TwentyMinute,
+    // Slot 0-4 within a 10 minute cycle (codes 0-4).
+    TenMinute(u8),
+    // Slot 0-9 within a 20 minute cycle (codes 5-14).
+    TwentyMinute(u8),
     BandCoordinated,
     NoSchedule,
     Tracker,
 }
 
+impl TimeSlot {
+    pub fn code(&self) -> u8 {
+        match self {
+            TimeSlot::TenMinute(slot) => *slot,
+            TimeSlot::TwentyMinute(slot) => 5 + *slot,
+            TimeSlot::BandCoordinated => 15,
+            TimeSlot::NoSchedule => 16,
+            TimeSlot::Tracker => 17,
+        }
+    }
+}
+
+impl TryFrom<u8> for TimeSlot {
+    type Error = anyhow::Error;
+
+    fn try_from(code: u8) -> Result<Self> {
+        Ok(match code {
+            0..=4 => TimeSlot::TenMinute(code),
+            5..=14 => TimeSlot::TwentyMinute(code - 5),
+            15 => TimeSlot::BandCoordinated,
+            16 => TimeSlot::NoSchedule,
+            17 => TimeSlot::Tracker,
+            _ => {
+                bail!("Bad time slot code {code}");
+            }
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum PrefixSuffix {
@@ -69,7 +166,7 @@ pub enum PrefixSuffix {
     None = b'N',
 }
 
-#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum Constellation {
     GPSOnly = b'G',
@@ -77,7 +174,7 @@ pub enum Constellation {
     All = b'A',
 }
 
-#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum Band {
     B2190m = 0,
@@ -100,6 +197,16 @@ pub enum Band {
     Open = 99,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, IntoPrimitive, FromPrimitive)]
+#[repr(u16)]
+pub enum ProductModel {
+    WsprTxLp1 = 1011,
+    WsprDesktop = 1012,
+    WsprMini = 1017,
+    #[num_enum(catch_all)]
+    Unknown(u16),
+}
+
 #[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum GpsLock {
@@ -262,17 +369,8 @@ impl TimeSlotOption {
     pub const CODE: &'static [u8] = b"OTS";
 
     fn parse(command_string: &str, args: &[u8]) -> Result<Response> {
-        let number: u16 = parse_number(command_string, args)?;
-        let time_slot = match number {
-            0..=4 => TimeSlot::TenMinute,
-            5..=14 => TimeSlot::TwentyMinute,
-            15 => TimeSlot::BandCoordinated,
-            16 => TimeSlot::NoSchedule,
-            17 => TimeSlot::Tracker,
-            _ => {
-                bail!("Bad time slot {:?}", args);
-            }
-        };
+        let code: u8 = parse_number(command_string, args)?;
+        let time_slot = TimeSlot::try_from(code)?;
         Ok(Response::TimeSlotOption(TimeSlotOption { time_slot }))
     }
 }
@@ -431,7 +529,8 @@ impl NameData {
 
 #[derive(Debug, Clone)]
 pub struct GeneratorFrequencyData {
-    pub hertz: f32,
+    // Kept as sent; 12 digits don't fit a u32 or, exactly, an f32.
+    pub centihertz: u64,
 }
 
 impl GeneratorFrequencyData {
@@ -440,12 +539,15 @@ impl GeneratorFrequencyData {
     pub const CODE: &'static [u8] = b"DGF";
 
     fn parse(command_string: &str, args: &[u8]) -> Result<Response> {
-        let centihertz: u32 = parse_number(command_string, args)?;
-        let hertz: f32 = centihertz as f32 / 100.;
+        let centihertz: u64 = parse_number(command_string, args)?;
         Ok(Response::GeneratorFrequencyData(GeneratorFrequencyData {
-            hertz,
+            centihertz,
         }))
     }
+
+    pub fn hertz(&self) -> f64 {
+        self.centihertz as f64 / 100.
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -470,7 +572,7 @@ impl ExternalReferenceFrequencyData {
 
 #[derive(Debug, Clone)]
 pub struct ProductModelNumberFactory {
-    pub model: u16, // TODO(ch): switch to enum
+    pub model: ProductModel,
 }
 
 impl ProductModelNumberFactory {
@@ -481,7 +583,7 @@ impl ProductModelNumberFactory {
     fn parse(command_string: &str, args: &[u8]) -> Result<Response> {
         Ok(Response::ProductModelNumberFactory(
             ProductModelNumberFactory {
-                model: parse_number(command_string, args)?,
+                model: parse_number::<u16>(command_string, args)?.into(),
             },
         ))
     }
@@ -489,64 +591,64 @@ impl ProductModelNumberFactory {
 
 #[derive(Debug, Clone)]
 pub struct HardwareVersionFactory {
-    pub hardware_version: String,
+    pub hardware_version: u8,
 }
 
 impl HardwareVersionFactory {
     // Factory Hardware Version [FHV] S/G Text 3 0-255
     pub const CODE: &'static [u8] = b"FHV";
 
-    fn parse(_command_string: &str, args: &[u8]) -> Result<Response> {
+    fn parse(command_string: &str, args: &[u8]) -> Result<Response> {
         Ok(Response::HardwareVersionFactory(HardwareVersionFactory {
-            hardware_version: ascii_bytes_to_string(args)?,
+            hardware_version: parse_number(command_string, args)?,
         }))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct HardwareRevisionFactory {
-    pub hardware_version: String,
+    pub hardware_revision: u8,
 }
 
 impl HardwareRevisionFactory {
     // Factory Hardware Revision [FHR] S/G Text 3 0-255
     pub const CODE: &'static [u8] = b"FHR";
 
-    fn parse(_command_string: &str, args: &[u8]) -> Result<Response> {
+    fn parse(command_string: &str, args: &[u8]) -> Result<Response> {
         Ok(Response::HardwareRevisionFactory(HardwareRevisionFactory {
-            hardware_version: ascii_bytes_to_string(args)?,
+            hardware_revision: parse_number(command_string, args)?,
         }))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct SoftwareVersionFactory {
-    pub software_version: String,
+    pub software_version: u8,
 }
 
 impl SoftwareVersionFactory {
     // Factory Software Version [FSV] G Text 3 0-255
     pub const CODE: &'static [u8] = b"FSV";
 
-    fn parse(_command_string: &str, args: &[u8]) -> Result<Response> {
+    fn parse(command_string: &str, args: &[u8]) -> Result<Response> {
         Ok(Response::SoftwareVersionFactory(SoftwareVersionFactory {
-            software_version: ascii_bytes_to_string(args)?,
+            software_version: parse_number(command_string, args)?,
         }))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct SoftwareRevisionFactory {
-    pub software_revision: String,
+    pub software_revision: u8,
 }
 
 impl SoftwareRevisionFactory {
     // Factory Software Revision [FSR] G Text 3 0-255
     pub const CODE: &'static [u8] = b"FSR";
 
-    fn parse(_command_string: &str, args: &[u8]) -> Result<Response> {
+    fn parse(command_string: &str, args: &[u8]) -> Result<Response> {
         Ok(Response::SoftwareRevisionFactory(SoftwareRevisionFactory {
-            software_revision: ascii_bytes_to_string(args)?,
+            software_revision: parse_number(command_string, args)?,
         }))
     }
 }
@@ -642,11 +744,20 @@ impl TimeGPS {
     pub const CODE: &'static [u8] = b"GTM";
 
     fn parse(_command_string: &str, args: &[u8]) -> Result<Response> {
-        // TODO(ch): parse this.
         Ok(Response::TimeGPS(TimeGPS {
             hhmmss: ascii_bytes_to_string(args)?,
         }))
     }
+
+    pub fn time(&self) -> Result<NaiveTime> {
+        NaiveTime::parse_from_str(self.hhmmss.trim(), "%H:%M:%S")
+            .with_context(|| format!("Failed to parse GPS time {:?}", self.hhmmss))
+    }
+
+    // The unit only reports time of day; the date comes from the host.
+    pub fn on_date(&self, date: NaiveDate) -> Result<DateTime<Utc>> {
+        Ok(date.and_time(self.time()?).and_utc())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -668,24 +779,47 @@ impl LockStatusGPS {
 
 #[derive(Debug, Clone)]
 pub struct SatelliteInfoGPS {
-    pub satellite_info: String,
+    pub id: u8,
+    // Degrees from true north.
+    pub azimuth: u16,
+    // Degrees above the horizon.
+    pub elevation: u8,
+    // dB-Hz; `None` if the satellite is in view but not tracked.
+    pub snr: Option<u8>,
 }
 
 impl SatelliteInfoGPS {
     // GPS Satellite data {GSI} Text2 Text3 Text2 Text2 - ID Az El SNR
     pub const CODE: &'static [u8] = b"GSI";
 
-    fn parse(_command_string: &str, args: &[u8]) -> Result<Response> {
-        // TODO(ch): parse this.
+    fn parse(command_string: &str, args: &[u8]) -> Result<Response> {
+        let fields: Vec<&[u8]> = args
+            .split(|c| *c == b' ')
+            .filter(|field| !field.is_empty())
+            .collect();
+        // The GPS leaves SNR empty for satellites it isn't tracking.
+        let (id_arg, azimuth_arg, elevation_arg, snr_arg) = match fields[..] {
+            [id, azimuth, elevation] => (id, azimuth, elevation, None),
+            [id, azimuth, elevation, snr] => (id, azimuth, elevation, Some(snr)),
+            _ => bail!("Bad args for GSI {:?}", args),
+        };
+        let snr = match snr_arg {
+            Some(snr_arg) => Some(parse_number(command_string, snr_arg)?).filter(|snr| *snr > 0),
+            None => None,
+        };
         Ok(Response::SatelliteInfoGPS(SatelliteInfoGPS {
-            satellite_info: ascii_bytes_to_string(args)?,
+            id: parse_number(command_string, id_arg)?,
+            azimuth: parse_number(command_string, azimuth_arg)?,
+            elevation: parse_number(command_string, elevation_arg)?,
+            snr,
         }))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct TransmitterFrequency {
-    pub hertz: f32,
+    // f64, as f32 can't hold HF frequencies to the hertz.
+    pub hertz: f64,
 }
 
 impl TransmitterFrequency {
@@ -695,7 +829,7 @@ impl TransmitterFrequency {
 
     fn parse(command_string: &str, args: &[u8]) -> Result<Response> {
         let centihertz: u64 = parse_number(command_string, args)?;
-        let hertz = centihertz as f32 / 100.;
+        let hertz = centihertz as f64 / 100.;
         Ok(Response::TransmitterFrequency(TransmitterFrequency {
             hertz,
         }))
@@ -726,15 +860,25 @@ impl TransmitterStatus {
 }
 
 #[derive(Debug, Clone)]
-pub struct MicrocontrollerPause {}
+pub struct MicrocontrollerPause {
+    pub duration: Duration,
+}
 
 impl MicrocontrollerPause {
     // Microcontroller Pause {MPS} Text 7 0-4,000,000Seconds
     pub const CODE: &'static [u8] = b"MPS";
 
-    fn parse(_command_string: &str, _args: &[u8]) -> Result<Response> {
-        // TODO(ch): implement
-        Ok(Response::MicrocontrollerPause(MicrocontrollerPause {}))
+    pub const MAX_SECONDS: u32 = 4_000_000;
+
+    fn parse(command_string: &str, args: &[u8]) -> Result<Response> {
+        let seconds: u32 = parse_number(command_string, args)?;
+        ensure!(
+            seconds <= Self::MAX_SECONDS,
+            "Pause of {seconds} s in {command_string} is out of range"
+        );
+        Ok(Response::MicrocontrollerPause(MicrocontrollerPause {
+            duration: Duration::from_secs(seconds as u64),
+        }))
     }
 }
 
@@ -808,7 +952,8 @@ impl TransmitterCurrentBand {
 
 #[derive(Debug, Clone)]
 pub struct TransmitterWSPRSymbol {
-    pub something: String,
+    pub band: Band,
+    pub symbol: u8,
 }
 
 impl TransmitterWSPRSymbol {
@@ -816,10 +961,24 @@ impl TransmitterWSPRSymbol {
     // symbol count 0-161
     pub const CODE: &'static [u8] = b"TWS";
 
-    fn parse(_command_string: &str, args: &[u8]) -> Result<Response> {
-        // TODO(ch): figure this out
+    pub const SYMBOLS: u8 = 162;
+
+    fn parse(command_string: &str, args: &[u8]) -> Result<Response> {
+        let mut fields = args.split(|c| *c == b' ').filter(|field| !field.is_empty());
+        let (Some(band_arg), Some(symbol_arg), None) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            bail!("Bad args for TWS {:?}", args);
+        };
+        let band: Band = parse_enum_from_number(command_string, band_arg)?;
+        let symbol: u8 = parse_number(command_string, symbol_arg)?;
+        ensure!(
+            symbol < Self::SYMBOLS,
+            "Symbol {symbol} in {command_string} is out of range"
+        );
         Ok(Response::TransmitterWSPRSymbol(TransmitterWSPRSymbol {
-            something: ascii_bytes_to_string(args)?,
+            band,
+            symbol,
         }))
     }
 }
@@ -986,28 +1145,109 @@ pub fn process_line(mut s: Vec<u8>) -> Result<Response> {
     }
 }
 
-fn write_code<RW>(port: &mut RW, code: &[u8])
+fn write_code<RW>(port: &mut RW, code: &[u8]) -> io::Result<()>
 where
     RW: io::Read + io::Write,
 {
-    const OPEN_BRACKET: &[u8] = b"[";
-    const CLOSE_BRACKET: &[u8] = b"]";
-    const LF: &[u8] = b"\n";
-
-    port.write_all(LF).expect("Failed to write.");
-    port.write_all(OPEN_BRACKET).expect("Failed to write.");
-    port.write_all(code).expect("Failed to write.");
-    port.write_all(CLOSE_BRACKET).expect("Failed to write.");
-    port.write_all(LF).expect("Failed to write.");
+    // One write, so the frame can't be split by another writer.
+    let mut frame = Vec::with_capacity(code.len() + 4);
+    frame.extend_from_slice(b"\n[");
+    frame.extend_from_slice(code);
+    frame.extend_from_slice(b"]\n");
+    port.write_all(&frame)?;
+    port.flush()
 }
 
-pub struct ZachtekDevice<'a> {
-    port: &'a mut Box<dyn SerialPort>,
-}
+// Command frames are "[CODE] G args" to get and "[CODE] S args" to set.
+fn write_command<RW>(port: &mut RW, code: &[u8], operation: u8, args: &[u8]) -> Result<()>
+where
+    RW: io::Read + io::Write,
+{
+    let mut frame = Vec::with_capacity(code.len() + args.len() + 8);
+    frame.extend_from_slice(b"\n[");
+    frame.extend_from_slice(code);
+    frame.extend_from_slice(b"] ");
+    frame.push(operation);
+    if !args.is_empty() {
+        frame.push(b' ');
+        frame.extend_from_slice(args);
+    }
+    frame.push(b'\n');
+    trace!("write: {:?}", AsciiStr::from_ascii(&frame));
+    port.write_all(&frame).context("Failed to write command")?;
+    port.flush().context("Failed to flush command")
+}
+
+/// Whether a `read_response` error is just the port's read timeout.
+pub fn is_timeout(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::TimedOut)
+}
+
+/// Whether a `read_response` error came from the port rather than from
+/// parsing what the unit sent.
+pub fn is_port_error(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<io::Error>().is_some() && !is_timeout(err)
+}
+
+pub const BAUD_RATE: u32 = 9_600;
+
+/// Open a serial port with the unit's settings (9600 8N1, no flow
+/// control).
+pub fn open_port(port_path: &str, timeout: Duration) -> Result<Box<dyn SerialPort>> {
+    serialport::new(port_path, BAUD_RATE)
+        .data_bits(serialport::DataBits::Eight)
+        .parity(serialport::Parity::None)
+        .stop_bits(serialport::StopBits::One)
+        .flow_control(serialport::FlowControl::None)
+        .timeout(timeout)
+        .open()
+        .with_context(|| format!("Failed to open serial port at {}", port_path))
+}
+
+pub struct ZachtekDevice {
+    port: Box<dyn SerialPort>,
+    model: Option<ProductModel>,
+    info: Option<DeviceInfo>,
+    // Where the port came from, so it can be reopened.
+    origin: Option<PortOrigin>,
+    backoff: Backoff,
+    disconnected: bool,
+    // Set while the unit is sleeping (MPS).
+    pause: Option<pause::Pause>,
+    poll: Option<PollThread>,
+    // Held for each frame written, by this and the poll thread.
+    write_lock: Arc<Mutex<()>>,
+}
+
+struct PollThread {
+    poll_sleep_interval: Duration,
+    // Dropping it stops the thread, cutting short any wait.
+    stop: Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl ZachtekDevice {
+    pub fn new(port: Box<dyn SerialPort>) -> Self {
+        Self {
+            port,
+            model: None,
+            info: None,
+            origin: None,
+            backoff: Backoff::default(),
+            disconnected: false,
+            pause: None,
+            poll: None,
+            write_lock: Arc::new(Mutex::new(())),
+        }
+    }
 
-impl<'a> ZachtekDevice<'a> {
-    pub fn new(port: &'a mut Box<dyn SerialPort>) -> Self {
-        Self { port }
+    /// Set the unit running. Call `handshake` as well to identify it.
+    pub fn connect(port: Box<dyn SerialPort>) -> Result<Self> {
+        let mut device = Self::new(port);
+        device.set_run()?;
+        device.clear_input()?;
+        Ok(device)
     }
 
     pub fn reset_device(&mut self) -> Result<()> {
@@ -1039,52 +1279,94 @@ impl<'a> ZachtekDevice<'a> {
         Ok(())
     }
 
-    fn poll_thread(mut port: Box<dyn SerialPort>, poll_sleep_interval: Duration) {
-        const CODES: &[&[u8]] = &[
-            CurrentModeCommand::CODE,
-            CurrentReferenceCommand::CODE,
-            TxPauseOption::CODE,
-            StartModeOption::CODE,
-            BandTxEnable::CODE,
-            LocationSourceOption::CODE,
-            LocatorPrecisionOption::CODE,
-            PowerEncodingOption::CODE,
-            TimeSlotOption::CODE,
-            PrefixSuffixOption::CODE,
-            ConstellationOption::CODE,
-            SuffixData::CODE,
-            PrefixData::CODE,
-            Locator4Data::CODE,
-            Locator6Data::CODE,
-            PowerData::CODE,
-            NameData::CODE,
-            GeneratorFrequencyData::CODE,
-            ExternalReferenceFrequencyData::CODE,
-            ProductModelNumberFactory::CODE,
-            HardwareVersionFactory::CODE,
-            HardwareRevisionFactory::CODE,
-            SoftwareVersionFactory::CODE,
-            SoftwareRevisionFactory::CODE,
-            ReferenceOscillatorFrequencyFactory::CODE,
-            LowPassFilterFactory::CODE,
-        ];
+    const POLL_CODES: &'static [&'static [u8]] = &[
+        CurrentModeCommand::CODE,
+        CurrentReferenceCommand::CODE,
+        TxPauseOption::CODE,
+        StartModeOption::CODE,
+        BandTxEnable::CODE,
+        LocationSourceOption::CODE,
+        LocatorPrecisionOption::CODE,
+        PowerEncodingOption::CODE,
+        TimeSlotOption::CODE,
+        PrefixSuffixOption::CODE,
+        ConstellationOption::CODE,
+        SuffixData::CODE,
+        PrefixData::CODE,
+        Locator4Data::CODE,
+        Locator6Data::CODE,
+        PowerData::CODE,
+        NameData::CODE,
+        GeneratorFrequencyData::CODE,
+        ExternalReferenceFrequencyData::CODE,
+        ProductModelNumberFactory::CODE,
+        HardwareVersionFactory::CODE,
+        HardwareRevisionFactory::CODE,
+        SoftwareVersionFactory::CODE,
+        SoftwareRevisionFactory::CODE,
+        ReferenceOscillatorFrequencyFactory::CODE,
+        LowPassFilterFactory::CODE,
+        MicrocontrollerVoltage::CODE,
+    ];
+
+    fn poll_thread(
+        mut port: Box<dyn SerialPort>,
+        codes: Vec<&'static [u8]>,
+        poll_sleep_interval: Duration,
+        stop: Receiver<()>,
+        write_lock: Arc<Mutex<()>>,
+    ) {
+        let stopped = |wait| !matches!(stop.recv_timeout(wait), Err(RecvTimeoutError::Timeout));
         loop {
-            for code in CODES {
-                write_code(&mut port, code);
-                port.flush().expect("Failed to write.");
-                std::thread::sleep(Duration::from_millis(500));
+            for code in &codes {
+                let written = {
+                    let _guard = write_lock.lock().expect("Write lock poisoned.");
+                    write_code(&mut port, code)
+                };
+                if let Err(e) = written {
+                    // The reader sees the same failure and reconnects,
+                    // which starts a new poll thread.
+                    warn!("Poll thread stopping: {e}");
+                    return;
+                }
+                if stopped(Duration::from_millis(500)) {
+                    return;
+                }
+            }
+            if stopped(poll_sleep_interval) {
+                return;
             }
-            std::thread::sleep(poll_sleep_interval);
         }
     }
 
-    pub fn start_poll_thread(&self, poll_sleep_interval: Duration) {
-        let _ = std::thread::spawn({
-            let port = self.port.try_clone().expect("Failed to clone port.");
+    pub fn start_poll_thread(&mut self, poll_sleep_interval: Duration) -> Result<()> {
+        self.stop_poll_thread();
+        let port = self.port.try_clone().context("Failed to clone port")?;
+        let (stop, stop_rx) = mpsc::channel();
+        let handle = std::thread::spawn({
+            let codes = Self::POLL_CODES.to_vec();
+            let write_lock = self.write_lock.clone();
             move || {
-                Self::poll_thread(port, poll_sleep_interval);
+                Self::poll_thread(port, codes, poll_sleep_interval, stop_rx, write_lock);
             }
         });
+        self.poll = Some(PollThread {
+            poll_sleep_interval,
+            stop,
+            handle,
+        });
+        Ok(())
+    }
+
+    /// Stop polling. Once this returns nothing more is polled, though
+    /// answers to earlier polls may still arrive.
+    pub fn stop_poll_thread(&mut self) {
+        if let Some(poll) = self.poll.take() {
+            drop(poll.stop);
+            if poll.handle.join().is_err() {
+                warn!("Poll thread panicked");
+            }
+        }
     }
 
     pub fn clear_input(&mut self) -> Result<()> {
@@ -1092,18 +1374,89 @@ impl<'a> ZachtekDevice<'a> {
         Ok(())
     }
 
+    pub fn get(&mut self, code: &[u8], args: &[u8]) -> Result<()> {
+        let _guard = self.write_lock.lock().expect("Write lock poisoned.");
+        write_command(&mut self.port, code, b'G', args)
+    }
+
+    pub fn set(&mut self, code: &[u8], args: &[u8]) -> Result<()> {
+        let write_lock = self.write_lock.clone();
+        let _guard = write_lock.lock().expect("Write lock poisoned.");
+        self.set_locked(code, args)
+    }
+
+    // `set` for callers already holding the write lock.
+    fn set_locked(&mut self, code: &[u8], args: &[u8]) -> Result<()> {
+        ensure_settable(code)?;
+        write_command(&mut self.port, code, b'S', args)
+    }
+
+    /// Read responses until `matcher` accepts one, skipping unrelated
+    /// and unparseable lines, for up to `MAX_SKIPPED_RESPONSES`.
+    pub fn wait_for<T>(&mut self, mut matcher: impl FnMut(&Response) -> Option<T>) -> Result<T> {
+        const MAX_SKIPPED_RESPONSES: usize = 64;
+        for _ in 0..MAX_SKIPPED_RESPONSES {
+            match process_line(self.read_line()?) {
+                Ok(response) => {
+                    if let Some(t) = matcher(&response) {
+                        return Ok(t);
+                    }
+                    trace!("wait_for: skipping {response:?}");
+                }
+                Err(err) => {
+                    warn!("wait_for: ignoring bad response: {err}");
+                }
+            }
+        }
+        bail!("No matching response after {MAX_SKIPPED_RESPONSES} responses");
+    }
+
+    /// Send a get and wait for the matching response.
+    pub fn query<T>(
+        &mut self,
+        code: &[u8],
+        args: &[u8],
+        matcher: impl FnMut(&Response) -> Option<T>,
+    ) -> Result<T> {
+        self.get(code, args)?;
+        self.wait_for(matcher)
+            .with_context(|| format!("No response to {:?}", AsciiStr::from_ascii(code)))
+    }
+
+    pub fn read_name(&mut self) -> Result<String> {
+        self.query(NameData::CODE, b"", |response| match response {
+            Response::NameData(data) => Some(data.name.trim().to_string()),
+            _ => None,
+        })
+    }
+
+    pub fn read_call_sign(&mut self) -> Result<String> {
+        self.query(CallSignData::CODE, b"", |response| match response {
+            Response::CallSignData(data) => Some(data.call_sign.trim().to_string()),
+            _ => None,
+        })
+    }
+
     pub fn read_response(&mut self) -> Result<Response> {
+        process_line(self.read_line()?)
+    }
+
+    fn read_line(&mut self) -> Result<Vec<u8>> {
         let mut buf = vec![];
         loop {
             let mut one_byte = [0u8];
             match self.port.read(&mut one_byte) {
+                Ok(0) => {
+                    // Some platforms report an unplugged port as EOF.
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
                 Ok(n_read) => {
                     ensure!(n_read == 1);
                     let byte = one_byte[0];
 
                     match byte {
                         b'\n' if !buf.is_empty() => {
-                            return process_line(buf);
+                            return Ok(buf);
                         }
                         b'\n' | b'\r' => {}
                         _ => {
@@ -1111,10 +1464,9 @@ impl<'a> ZachtekDevice<'a> {
                         }
                     }
                 }
-                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
-                    warn!("Error: Timeout on serial port");
-                    //return Err(e.into());
-                    bail!("timeout");
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                    debug!("Timeout on serial port");
+                    return Err(e.into());
                 }
                 Err(e) => {
                     error!("Error: Failed to read from serial port: {}", e);
@@ -1124,3 +1476,57 @@ impl<'a> ZachtekDevice<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Response> {
+        process_line(line.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn pause_is_parsed_in_seconds() {
+        let Response::MicrocontrollerPause(pause) = parse("{MPS} 0000600\r\n").unwrap() else {
+            panic!("not a pause");
+        };
+        assert_eq!(pause.duration, Duration::from_secs(600));
+        assert!(parse("{MPS} 4000001").is_err());
+        assert!(parse("{MPS} soon").is_err());
+    }
+
+    #[test]
+    fn symbol_is_parsed_with_band() {
+        let Response::TransmitterWSPRSymbol(symbol) = parse("{TWS} 06 161").unwrap() else {
+            panic!("not a symbol");
+        };
+        assert_eq!(symbol.band, Band::B20m);
+        assert_eq!(symbol.symbol, 161);
+        assert!(parse("{TWS} 06 162").is_err());
+        assert!(parse("{TWS} 06").is_err());
+        assert!(parse("{TWS} 06 001 2").is_err());
+    }
+
+    #[test]
+    fn satellite_without_snr_is_untracked() {
+        let Response::SatelliteInfoGPS(satellite) = parse("{GSI} 05 120 45 38").unwrap() else {
+            panic!("not a satellite");
+        };
+        assert_eq!(
+            (
+                satellite.id,
+                satellite.azimuth,
+                satellite.elevation,
+                satellite.snr
+            ),
+            (5, 120, 45, Some(38))
+        );
+        for line in ["{GSI} 05 120 45", "{GSI} 05 120 45 00"] {
+            let Response::SatelliteInfoGPS(satellite) = parse(line).unwrap() else {
+                panic!("not a satellite");
+            };
+            assert_eq!(satellite.snr, None);
+        }
+        assert!(parse("{GSI} 05 120").is_err());
+    }
+}
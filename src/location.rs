@@ -0,0 +1,304 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::{
+    LatLon, LocationSource, LocationSourceOption, Locator4Data, Locator6Data, Response,
+    ZachtekDevice,
+};
+
+// A 4 character square is 2 by 1 degrees, so a point in it can be up
+// to about 125 km from its centre (at the equator). Allow that, so a
+// 4 character manual locator isn't flagged for a unit inside it.
+pub const DEFAULT_MAX_LOCATOR_DISTANCE_KM: f64 = 150.;
+
+impl FromStr for LocationSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.trim().to_ascii_lowercase().as_str() {
+            "gps" => LocationSource::Gps,
+            "manual" => LocationSource::Manual,
+            _ => bail!("Unknown location source '{s}', expected gps or manual"),
+        })
+    }
+}
+
+/// The manual and GPS locators are further apart than allowed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocatorMismatch {
+    pub time: DateTime<Utc>,
+    pub manual: String,
+    pub gps: String,
+    pub distance_km: f64,
+}
+
+impl fmt::Display for LocatorMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: manual locator {} is {:.0} km from GPS locator {}",
+            self.time, self.manual, self.distance_km, self.gps
+        )
+    }
+}
+
+/// Compares the manual locator (DL4/DL6) with the one the GPS works
+/// out (GL4/GL6). The most precise of each is used.
+#[derive(Debug, Clone)]
+pub struct LocatorMonitor {
+    pub max_distance_km: f64,
+    pub source: Option<LocationSource>,
+    pub manual_4: Option<String>,
+    pub manual_6: Option<String>,
+    pub gps_4: Option<String>,
+    pub gps_6: Option<String>,
+    pub mismatches: Vec<LocatorMismatch>,
+    disagreeing: bool,
+}
+
+impl Default for LocatorMonitor {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_LOCATOR_DISTANCE_KM)
+    }
+}
+
+impl LocatorMonitor {
+    pub fn new(max_distance_km: f64) -> Self {
+        Self {
+            max_distance_km,
+            source: None,
+            manual_4: None,
+            manual_6: None,
+            gps_4: None,
+            gps_6: None,
+            mismatches: Vec::new(),
+            disagreeing: false,
+        }
+    }
+
+    /// Feed a response received at `time`. Returns the mismatch, if
+    /// this response moves the locators too far apart.
+    pub fn check(&mut self, response: &Response, time: DateTime<Utc>) -> Option<LocatorMismatch> {
+        let (locator, value) = match response {
+            Response::LocationSourceOption(option) => {
+                self.source = Some(option.location_source);
+                return None;
+            }
+            Response::Locator4Data(data) => (&mut self.manual_4, &data.locator_4),
+            Response::Locator6Data(data) => (&mut self.manual_6, &data.locator_6),
+            Response::Locator4GPS(locator) => (&mut self.gps_4, &locator.maidenhead_4),
+            Response::Locator6GPS(locator) => (&mut self.gps_6, &locator.maidenhead_6),
+            _ => return None,
+        };
+        // Empty until set, or until the GPS has a fix.
+        let value = value.trim();
+        if value.is_empty() || locator.as_deref() == Some(value) {
+            return None;
+        }
+        *locator = Some(value.to_string());
+
+        let distance_km = self.distance_km()?;
+        if distance_km <= self.max_distance_km {
+            if std::mem::take(&mut self.disagreeing) {
+                info!("Manual and GPS locators agree again ({distance_km:.0} km apart)");
+            }
+            return None;
+        }
+        if std::mem::replace(&mut self.disagreeing, true) {
+            return None;
+        }
+        let mismatch = LocatorMismatch {
+            time,
+            manual: self.manual()?.to_string(),
+            gps: self.gps()?.to_string(),
+            distance_km,
+        };
+        warn!("{mismatch}");
+        self.mismatches.push(mismatch.clone());
+        Some(mismatch)
+    }
+
+    pub fn manual(&self) -> Option<&str> {
+        self.manual_6.as_deref().or(self.manual_4.as_deref())
+    }
+
+    pub fn gps(&self) -> Option<&str> {
+        self.gps_6.as_deref().or(self.gps_4.as_deref())
+    }
+
+    /// Distance between the centres of the manual and GPS locators, if
+    /// both are known and valid.
+    pub fn distance_km(&self) -> Option<f64> {
+        let manual = LatLon::from_locator(self.manual()?).ok()?;
+        let gps = LatLon::from_locator(self.gps()?).ok()?;
+        Some(manual.distance_km(&gps))
+    }
+}
+
+// The subsquare to write to DL6 for a 4 or 6 character grid. The unit
+// sends DL6 whenever it holds one, so a 4 character grid can't leave
+// an old subsquare there. A square has 24 by 24 subsquares, so there
+// is no middle one; this takes the one north-east of the centre.
+fn manual_locator_6(locator: &str) -> Result<String> {
+    let locator = locator.trim();
+    LatLon::from_locator(locator)?;
+    match locator.len() {
+        6 => Ok(locator.to_string()),
+        4 => Ok(format!("{locator}MM")),
+        _ => bail!("Manual locator '{locator}' must have 4 or 6 characters"),
+    }
+}
+
+impl ZachtekDevice {
+    pub fn read_location_source(&mut self) -> Result<LocationSource> {
+        self.query(LocationSourceOption::CODE, b"", |response| match response {
+            Response::LocationSourceOption(option) => Some(option.location_source),
+            _ => None,
+        })
+    }
+
+    pub fn write_location_source(&mut self, source: LocationSource) -> Result<()> {
+        self.set_verified(LocationSourceOption::CODE, &[source.into()])
+    }
+
+    /// The manual locator: DL6, or DL4 if that is all there is.
+    pub fn read_manual_locator(&mut self) -> Result<String> {
+        let locator = self.query(Locator6Data::CODE, b"", |response| match response {
+            Response::Locator6Data(data) => Some(data.locator_6.trim().to_string()),
+            _ => None,
+        })?;
+        if !locator.is_empty() {
+            return Ok(locator);
+        }
+        self.query(Locator4Data::CODE, b"", |response| match response {
+            Response::Locator4Data(data) => Some(data.locator_4.trim().to_string()),
+            _ => None,
+        })
+    }
+
+    /// Set the manual locators from a 4 or 6 character grid. DL4 gets
+    /// the square and DL6 the subsquare, see `manual_locator_6`.
+    pub fn write_manual_locator(&mut self, locator: &str) -> Result<()> {
+        self.write_manual_locators(&manual_locator_6(locator)?)
+    }
+
+    /// Set the manual locators to the subsquare containing `position`.
+    pub fn write_manual_position(&mut self, position: LatLon) -> Result<()> {
+        self.write_manual_locators(&position.to_locator(6)?)
+    }
+
+    // Both are sent in capitals, as they appear in WSPR messages.
+    fn write_manual_locators(&mut self, locator_6: &str) -> Result<()> {
+        let locator_6 = locator_6.to_ascii_uppercase();
+        self.set_verified(Locator4Data::CODE, &locator_6.as_bytes()[..4])?;
+        self.set_verified(Locator6Data::CODE, locator_6.as_bytes())?;
+        info!("Manual locator set to {locator_6}");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Locator4GPS, Locator6GPS};
+    use chrono::TimeZone;
+
+    fn at(second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, second).unwrap()
+    }
+
+    fn manual(locator: &str) -> [Response; 2] {
+        [
+            Response::Locator4Data(Locator4Data {
+                locator_4: locator[..4].to_string(),
+            }),
+            Response::Locator6Data(Locator6Data {
+                locator_6: locator.to_string(),
+            }),
+        ]
+    }
+
+    fn gps(locator: &str) -> [Response; 2] {
+        [
+            Response::Locator4GPS(Locator4GPS {
+                maidenhead_4: locator[..4].to_string(),
+            }),
+            Response::Locator6GPS(Locator6GPS {
+                maidenhead_6: locator.to_string(),
+            }),
+        ]
+    }
+
+    fn feed(monitor: &mut LocatorMonitor, responses: &[Response], second: u32) -> usize {
+        responses
+            .iter()
+            .filter_map(|response| monitor.check(response, at(second)))
+            .count()
+    }
+
+    #[test]
+    fn four_characters_replace_the_subsquare() {
+        let mut monitor = LocatorMonitor::default();
+        feed(&mut monitor, &manual("FN42HN"), 0);
+        let locator_6 = manual_locator_6(" jo65 ").unwrap();
+        assert_eq!(locator_6.to_ascii_uppercase(), "JO65MM");
+        // What the unit reports back after the write.
+        feed(&mut monitor, &manual(&locator_6.to_ascii_uppercase()), 1);
+        assert_eq!(monitor.manual(), Some("JO65MM"));
+        let square = LatLon::from_locator("JO65").unwrap();
+        let subsquare = LatLon::from_locator(monitor.manual().unwrap()).unwrap();
+        assert!(square.distance_km(&subsquare) < 10.);
+    }
+
+    #[test]
+    fn bad_manual_locators_are_refused() {
+        assert_eq!(manual_locator_6("JO65ab").unwrap(), "JO65ab");
+        assert!(manual_locator_6("JO").is_err());
+        assert!(manual_locator_6("JO65ab12").is_err());
+        assert!(manual_locator_6("ZZ99").is_err());
+    }
+
+    #[test]
+    fn distant_locators_are_reported_once() {
+        let mut monitor = LocatorMonitor::new(100.);
+        feed(&mut monitor, &manual("JO65AB"), 0);
+        assert_eq!(feed(&mut monitor, &gps("JO65AC"), 1), 0);
+        assert_eq!(feed(&mut monitor, &gps("JO75AB"), 2), 1);
+        assert_eq!(feed(&mut monitor, &gps("JO85AB"), 3), 0);
+        let mismatch = &monitor.mismatches[0];
+        assert_eq!(
+            (mismatch.manual.as_str(), mismatch.gps.as_str()),
+            ("JO65AB", "JO75AB")
+        );
+        // Agreeing again allows the next mismatch to be reported.
+        feed(&mut monitor, &gps("JO65AA"), 4);
+        assert_eq!(feed(&mut monitor, &gps("JO75AA"), 5), 1);
+        assert_eq!(monitor.mismatches.len(), 2);
+    }
+
+    #[test]
+    fn empty_gps_locator_is_ignored() {
+        let mut monitor = LocatorMonitor::default();
+        feed(&mut monitor, &manual("JO65AB"), 0);
+        feed(&mut monitor, &gps("      "), 1);
+        assert_eq!(monitor.gps(), None);
+        assert_eq!(monitor.distance_km(), None);
+    }
+
+    #[test]
+    fn default_allows_a_whole_square() {
+        let mut monitor = LocatorMonitor::default();
+        monitor.check(
+            &Response::Locator4Data(Locator4Data {
+                locator_4: "JO65".into(),
+            }),
+            at(0),
+        );
+        // Corner of the square.
+        assert_eq!(feed(&mut monitor, &gps("JO65AA"), 1), 0);
+        assert!(monitor.mismatches.is_empty());
+    }
+}
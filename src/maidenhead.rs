@@ -0,0 +1,190 @@
+use anyhow::{bail, ensure, Result};
+use std::fmt;
+
+// Mean Earth radius used for great circle distances.
+const EARTH_RADIUS_KM: f64 = 6371.;
+
+// Degrees of longitude and latitude covered by each character pair:
+// field (A-R), square (0-9), subsquare (a-x) and extended square (0-9).
+const STEPS: [(f64, f64); 4] = [
+    (20., 10.),
+    (2., 1.),
+    (2. / 24., 1. / 24.),
+    (2. / 240., 1. / 240.),
+];
+
+/// A position in degrees, north and east positive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatLon {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl LatLon {
+    pub fn new(lat: f64, lon: f64) -> Result<Self> {
+        ensure!(
+            (-90. ..=90.).contains(&lat) && (-180. ..=180.).contains(&lon),
+            "Position {lat}, {lon} is out of range"
+        );
+        Ok(Self { lat, lon })
+    }
+
+    /// Centre of a 2, 4, 6 or 8 character Maidenhead locator.
+    pub fn from_locator(locator: &str) -> Result<Self> {
+        let bytes = locator.trim().to_ascii_uppercase().into_bytes();
+        ensure!(
+            matches!(bytes.len(), 2 | 4 | 6 | 8),
+            "Locator '{locator}' must have 2, 4, 6 or 8 characters"
+        );
+        let mut lon = -180.;
+        let mut lat = -90.;
+        for (pair, (chars, (lon_step, lat_step))) in bytes.chunks(2).zip(STEPS).enumerate() {
+            let (lon_index, lat_index) = match pair {
+                0 => (letter(chars[0], b'R')?, letter(chars[1], b'R')?),
+                2 => (letter(chars[0], b'X')?, letter(chars[1], b'X')?),
+                _ => (digit(chars[0])?, digit(chars[1])?),
+            };
+            lon += lon_index as f64 * lon_step;
+            lat += lat_index as f64 * lat_step;
+        }
+        let (lon_step, lat_step) = STEPS[bytes.len() / 2 - 1];
+        Self::new(lat + lat_step / 2., lon + lon_step / 2.)
+    }
+
+    /// Maidenhead locator of the square containing this position, with
+    /// 2, 4, 6 or 8 characters.
+    pub fn to_locator(&self, length: usize) -> Result<String> {
+        ensure!(
+            matches!(length, 2 | 4 | 6 | 8),
+            "Locator length must be 2, 4, 6 or 8, not {length}"
+        );
+        // Keep the poles and antimeridian inside the last square.
+        let mut lon = (self.lon + 180.).clamp(0., 360. - 1e-9);
+        let mut lat = (self.lat + 90.).clamp(0., 180. - 1e-9);
+        let mut locator = String::with_capacity(length);
+        for (pair, (lon_step, lat_step)) in STEPS.into_iter().enumerate().take(length / 2) {
+            let lon_index = (lon / lon_step).floor();
+            let lat_index = (lat / lat_step).floor();
+            lon -= lon_index * lon_step;
+            lat -= lat_index * lat_step;
+            let base = match pair {
+                0 => b'A',
+                2 => b'a',
+                _ => b'0',
+            };
+            locator.push((base + lon_index as u8) as char);
+            locator.push((base + lat_index as u8) as char);
+        }
+        Ok(locator)
+    }
+
+    /// Great circle distance in kilometres.
+    pub fn distance_km(&self, other: &LatLon) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+        let a = (dlat / 2.).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.).sin().powi(2);
+        2. * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+impl fmt::Display for LatLon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.5}, {:.5}", self.lat, self.lon)
+    }
+}
+
+/// Distance in kilometres between the centres of two locators.
+pub fn locator_distance_km(a: &str, b: &str) -> Result<f64> {
+    Ok(LatLon::from_locator(a)?.distance_km(&LatLon::from_locator(b)?))
+}
+
+fn letter(c: u8, last: u8) -> Result<u8> {
+    if !(b'A'..=last).contains(&c) {
+        bail!("Bad locator character '{}'", c as char);
+    }
+    Ok(c - b'A')
+}
+
+fn digit(c: u8) -> Result<u8> {
+    if !c.is_ascii_digit() {
+        bail!("Bad locator character '{}'", c as char);
+    }
+    Ok(c - b'0')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} != {b}");
+    }
+
+    #[test]
+    fn from_locator_gives_square_centre() {
+        let position = LatLon::from_locator("JO01").unwrap();
+        assert_near(position.lat, 51.5);
+        assert_near(position.lon, 1.);
+        let position = LatLon::from_locator("AA").unwrap();
+        assert_near(position.lat, -85.);
+        assert_near(position.lon, -170.);
+    }
+
+    #[test]
+    fn locators_round_trip() {
+        for locator in [
+            "JO01", "JO01mk", "FN31pr", "QF56od", "JO01mk27", "AA00aa", "RR99xx",
+        ] {
+            let position = LatLon::from_locator(locator).unwrap();
+            assert_eq!(position.to_locator(locator.len()).unwrap(), locator);
+        }
+    }
+
+    #[test]
+    fn from_locator_ignores_case() {
+        assert_eq!(
+            LatLon::from_locator("jo01MK").unwrap(),
+            LatLon::from_locator("JO01mk").unwrap()
+        );
+    }
+
+    #[test]
+    fn poles_and_antimeridian_stay_in_range() {
+        let north_east = LatLon::new(90., 180.).unwrap();
+        assert_eq!(north_east.to_locator(6).unwrap(), "RR99xx");
+        let south_west = LatLon::new(-90., -180.).unwrap();
+        assert_eq!(south_west.to_locator(6).unwrap(), "AA00aa");
+    }
+
+    #[test]
+    fn square_edges_belong_to_the_square_above() {
+        // 0 degrees east, 50 degrees north is the corner of JO00.
+        let corner = LatLon::new(50., 0.).unwrap();
+        assert_eq!(corner.to_locator(4).unwrap(), "JO00");
+        let below = LatLon::new(50. - 1e-6, -1e-6).unwrap();
+        assert_eq!(below.to_locator(4).unwrap(), "IN99");
+    }
+
+    #[test]
+    fn rejects_bad_locators() {
+        for locator in ["", "J", "JO0", "JS01", "JO0A", "JO01my", "JO01mk2"] {
+            assert!(LatLon::from_locator(locator).is_err(), "{locator}");
+        }
+        assert!(LatLon::new(91., 0.).is_err());
+        assert!(LatLon::new(0., -181.).is_err());
+        assert!(LatLon::new(0., 0.).unwrap().to_locator(5).is_err());
+    }
+
+    #[test]
+    fn distance_is_great_circle() {
+        let origin = LatLon::new(0., 0.).unwrap();
+        // One degree of arc.
+        assert!((origin.distance_km(&LatLon::new(1., 0.).unwrap()) - 111.195).abs() < 0.001);
+        let antipode = LatLon::new(0., 180.).unwrap();
+        assert!(
+            (origin.distance_km(&antipode) - EARTH_RADIUS_KM * std::f64::consts::PI).abs() < 1e-6
+        );
+        assert_eq!(locator_distance_km("JO01", "jo01").unwrap(), 0.);
+    }
+}
@@ -0,0 +1,292 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::{DeviceInfo, DiscoveredDevice, PortOrigin, Response, ZachtekDevice};
+
+/// Serial number, or name (DNM) for units without one.
+pub type DeviceId = String;
+
+#[derive(Debug)]
+pub enum Event {
+    Response(Response),
+    // A line that could not be parsed.
+    Error(String),
+    // The port failed; the device will try to reconnect.
+    Disconnected(String),
+    // The port was reopened, and the unit identified again if it had
+    // been before.
+    Reconnected(Option<DeviceInfo>),
+    // A pause (MPS) has run its course.
+    Resumed,
+}
+
+#[derive(Debug)]
+pub struct DeviceEvent {
+    pub device: DeviceId,
+    pub time: DateTime<Utc>,
+    pub event: Event,
+}
+
+struct ManagedDevice {
+    info: Arc<Mutex<DeviceInfo>>,
+    stop: Arc<AtomicBool>,
+    // Settings for the reader to make between reads.
+    requests: Sender<SetRequest>,
+    reader: JoinHandle<()>,
+}
+
+struct SetRequest {
+    code: Vec<u8>,
+    args: Vec<u8>,
+    reply: Sender<Result<()>>,
+}
+
+/// Owns several units, reads them all concurrently and merges their
+/// responses into a single stream of events tagged by device.
+pub struct DeviceManager {
+    devices: BTreeMap<DeviceId, ManagedDevice>,
+    poll_sleep_interval: Option<Duration>,
+    events_tx: Sender<DeviceEvent>,
+    events_rx: Receiver<DeviceEvent>,
+}
+
+impl DeviceManager {
+    /// With `poll_sleep_interval` set, every unit added is also polled.
+    pub fn new(poll_sleep_interval: Option<Duration>) -> Self {
+        let (events_tx, events_rx) = mpsc::channel();
+        Self {
+            devices: BTreeMap::new(),
+            poll_sleep_interval,
+            events_tx,
+            events_rx,
+        }
+    }
+
+    /// Start reading a connected and identified unit. It is keyed by its USB serial
+    /// number if known, otherwise by its name.
+    pub fn add(&mut self, mut device: ZachtekDevice) -> Result<DeviceId> {
+        let id = match device.serial_number() {
+            Some(serial_number) => serial_number.to_string(),
+            None => device.read_name()?,
+        };
+        if self.devices.contains_key(&id) {
+            bail!("Device {id} is already managed");
+        }
+        let info = device
+            .info()
+            .cloned()
+            .context("Device has not been identified")?;
+        info!("Managing {id}: {info}");
+        let info = Arc::new(Mutex::new(info));
+        let (requests_tx, requests_rx) = mpsc::channel();
+        if let Some(poll_sleep_interval) = self.poll_sleep_interval {
+            device.start_poll_thread(poll_sleep_interval)?;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let reader = std::thread::spawn({
+            let reader = Reader {
+                device,
+                id: id.clone(),
+                info: info.clone(),
+                requests: requests_rx,
+                stop: stop.clone(),
+                events_tx: self.events_tx.clone(),
+            };
+            move || reader.run()
+        });
+        self.devices.insert(
+            id.clone(),
+            ManagedDevice {
+                info,
+                stop,
+                requests: requests_tx,
+                reader,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Open and identify the unit on `port_name`, and start reading it.
+    pub fn open(&mut self, port_name: &str, timeout: Duration) -> Result<DeviceId> {
+        let mut device = ZachtekDevice::open(port_name, timeout)?;
+        device.handshake()?;
+        self.add(device)
+    }
+
+    /// Open a unit found by `discover`, matching it by its USB serial
+    /// number in case its port name has changed since.
+    pub fn add_discovered(
+        &mut self,
+        found: &DiscoveredDevice,
+        timeout: Duration,
+    ) -> Result<DeviceId> {
+        let mut device = ZachtekDevice::open_origin(PortOrigin::from_discovered(found, timeout))?;
+        device.handshake()?;
+        self.add(device)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = &DeviceId> {
+        self.devices.keys()
+    }
+
+    pub fn info(&self, id: &str) -> Option<DeviceInfo> {
+        let device = self.devices.get(id)?;
+        let info = device.info.lock().expect("Info lock poisoned.");
+        Some(info.clone())
+    }
+
+    /// Block until any unit produces an event.
+    pub fn recv(&self) -> Result<DeviceEvent> {
+        Ok(self.events_rx.recv()?)
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<DeviceEvent> {
+        self.events_rx.recv_timeout(timeout).ok()
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = DeviceEvent> + '_ {
+        self.events_rx.iter()
+    }
+
+    /// Set `code` to `args` on the given units, or on all of them if
+    /// `targets` is `None`, checking that each echoes it back. Each
+    /// unit's reader makes the setting between reads, so the units are
+    /// set concurrently; responses read while waiting for the echo are
+    /// not passed on as events. A unit that is reconnecting fails
+    /// straight away. Returns every target's outcome.
+    pub fn broadcast(
+        &mut self,
+        targets: Option<&[DeviceId]>,
+        code: &[u8],
+        args: &[u8],
+    ) -> Result<BTreeMap<DeviceId, Result<()>>> {
+        if let Some(targets) = targets {
+            if let Some(unknown) = targets.iter().find(|id| !self.devices.contains_key(*id)) {
+                bail!("Unknown device {unknown}");
+            }
+        }
+        let replies: Vec<(DeviceId, Receiver<Result<()>>)> = self
+            .devices
+            .iter()
+            .filter(|(id, _)| targets.is_none_or(|targets| targets.contains(id)))
+            .map(|(id, device)| {
+                let (reply_tx, reply_rx) = mpsc::channel();
+                // A reader that has stopped drops the request, and
+                // with it the reply.
+                let _ = device.requests.send(SetRequest {
+                    code: code.to_vec(),
+                    args: args.to_vec(),
+                    reply: reply_tx,
+                });
+                (id.clone(), reply_rx)
+            })
+            .collect();
+        Ok(replies
+            .into_iter()
+            .map(|(id, reply)| {
+                let result = reply
+                    .recv()
+                    .unwrap_or_else(|_| Err(anyhow!("{id} is no longer being read")));
+                if let Err(err) = &result {
+                    warn!("{id}: {err:#}");
+                }
+                (id, result)
+            })
+            .collect())
+    }
+
+    /// Stop reading a unit and forget it.
+    pub fn remove(&mut self, id: &str) -> Option<DeviceInfo> {
+        let device = self.devices.remove(id)?;
+        device.stop.store(true, Ordering::Relaxed);
+        let _ = device.reader.join();
+        let info = device.info.lock().expect("Info lock poisoned.");
+        Some(info.clone())
+    }
+}
+
+struct Reader {
+    device: ZachtekDevice,
+    id: DeviceId,
+    info: Arc<Mutex<DeviceInfo>>,
+    requests: Receiver<SetRequest>,
+    stop: Arc<AtomicBool>,
+    events_tx: Sender<DeviceEvent>,
+}
+
+impl Reader {
+    fn run(mut self) {
+        while !self.stop.load(Ordering::Relaxed) {
+            self.serve_requests();
+            if self.device.is_disconnected() {
+                if !self.reconnect() {
+                    break;
+                }
+                continue;
+            }
+            let event = match self.device.read_event() {
+                Ok(None) => continue,
+                Ok(Some(event)) => event,
+                Err(err) => Event::Error(format!("{err:#}")),
+            };
+            if !self.send(event) {
+                break;
+            }
+        }
+        self.device.stop_poll_thread();
+    }
+
+    // Reconnect, failing settings asked for meanwhile and giving up if
+    // the reader is stopped. False once the manager is gone.
+    fn reconnect(&mut self) -> bool {
+        let id = &self.id;
+        let requests = &self.requests;
+        let stop = &self.stop;
+        let event = match self.device.reconnect_with(|| {
+            while let Ok(request) = requests.try_recv() {
+                let _ = request.reply.send(Err(anyhow!("{id} is disconnected")));
+            }
+            stop.load(Ordering::Relaxed)
+        }) {
+            Ok(()) => {
+                let info = self.device.info().cloned();
+                if let Some(info) = &info {
+                    *self.info.lock().expect("Info lock poisoned.") = info.clone();
+                }
+                Event::Reconnected(info)
+            }
+            Err(err) => {
+                warn!("{}: {err:#}", self.id);
+                Event::Error(format!("{err:#}"))
+            }
+        };
+        self.send(event)
+    }
+
+    // False once the manager is gone.
+    fn send(&self, event: Event) -> bool {
+        let event = DeviceEvent {
+            device: self.id.clone(),
+            time: Utc::now(),
+            event,
+        };
+        self.events_tx.send(event).is_ok()
+    }
+
+    fn serve_requests(&mut self) {
+        while let Ok(request) = self.requests.try_recv() {
+            let result = self
+                .device
+                .set_verified(&request.code, &request.args)
+                .with_context(|| format!("Failed to configure {}", self.id));
+            let _ = request.reply.send(result);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::Result;
+use std::fmt;
+use tracing::warn;
+
+use crate::{
+    Band, BandSet, Constellation, FilterBank, ProductModel, ProductModelNumberFactory, Response,
+    ZachtekDevice,
+};
+
+/// What a product model can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub filter_banks: &'static [FilterBank],
+    pub bands: BandSet,
+    pub external_reference: bool,
+    pub constellations: &'static [Constellation],
+}
+
+impl Capabilities {
+    // Used for models we don't know, so nothing is refused.
+    pub const PERMISSIVE: Capabilities = Capabilities {
+        filter_banks: &FilterBank::ALL,
+        bands: BandSet::ALL,
+        external_reference: true,
+        constellations: &[
+            Constellation::GPSOnly,
+            Constellation::BeiDouOnly,
+            Constellation::All,
+        ],
+    };
+
+    pub fn supports_band(&self, band: Band) -> bool {
+        self.bands.contains(band)
+    }
+
+    pub fn supports_filter_bank(&self, filter_bank: FilterBank) -> bool {
+        self.filter_banks.contains(&filter_bank)
+    }
+
+    pub fn supports_constellation(&self, constellation: Constellation) -> bool {
+        self.constellations.contains(&constellation)
+    }
+}
+
+impl ProductModel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProductModel::WsprTxLp1 => "WSPR-TX LP1",
+            ProductModel::WsprDesktop => "WSPR Desktop",
+            ProductModel::WsprMini => "WSPR Mini",
+            ProductModel::Unknown(_) => "Unknown",
+        }
+    }
+
+    // The API document (see `CODES`) gives the model numbers but not
+    // what each model has, so these are unconfirmed and the
+    // `warn_if_*` checks only warn when a command falls outside them.
+    pub fn capabilities(&self) -> Capabilities {
+        match self {
+            // Single band, filter on the board.
+            ProductModel::WsprTxLp1 => Capabilities {
+                filter_banks: &[FilterBank::A],
+                // 160m-10m.
+                bands: BandSet::from_bits(0b0000_0111_1111_1100),
+                external_reference: false,
+                constellations: &[Constellation::GPSOnly],
+            },
+            // Four switched filter banks and a reference input.
+            ProductModel::WsprDesktop => Capabilities {
+                filter_banks: &FilterBank::ALL,
+                // 2190m-2m.
+                bands: BandSet::from_bits(0b0011_1111_1111_1111),
+                external_reference: true,
+                constellations: &[
+                    Constellation::GPSOnly,
+                    Constellation::BeiDouOnly,
+                    Constellation::All,
+                ],
+            },
+            // Two switched filter banks.
+            ProductModel::WsprMini => Capabilities {
+                filter_banks: &[FilterBank::A, FilterBank::B],
+                // 160m-6m.
+                bands: BandSet::from_bits(0b0000_1111_1111_1100),
+                external_reference: false,
+                constellations: &[
+                    Constellation::GPSOnly,
+                    Constellation::BeiDouOnly,
+                    Constellation::All,
+                ],
+            },
+            ProductModel::Unknown(_) => Capabilities::PERMISSIVE,
+        }
+    }
+}
+
+impl fmt::Display for ProductModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name(), u16::from(*self))
+    }
+}
+
+impl ZachtekDevice {
+    /// Read the product model (FPN) and remember it so later commands
+    /// can be checked against the model's capabilities.
+    pub fn read_model(&mut self) -> Result<ProductModel> {
+        let model = self.query(
+            ProductModelNumberFactory::CODE,
+            b"",
+            |response| match response {
+                Response::ProductModelNumberFactory(factory) => Some(factory.model),
+                _ => None,
+            },
+        )?;
+        if let ProductModel::Unknown(number) = model {
+            warn!("Unknown product model {number}, not restricting commands");
+        }
+        self.model = Some(model);
+        Ok(model)
+    }
+
+    pub fn model(&self) -> Option<ProductModel> {
+        self.model
+    }
+
+    /// Capabilities of the unit; permissive until the model is read.
+    /// Unconfirmed, see `ProductModel::capabilities`.
+    pub fn capabilities(&self) -> Capabilities {
+        self.model
+            .map(|model| model.capabilities())
+            .unwrap_or(Capabilities::PERMISSIVE)
+    }
+
+    pub fn warn_if_band_unsupported(&self, band: Band) {
+        if !self.capabilities().supports_band(band) {
+            warn!("{} may not support the {band} band", self.model_name());
+        }
+    }
+
+    pub fn warn_if_filter_bank_unsupported(&self, filter_bank: FilterBank) {
+        if !self.capabilities().supports_filter_bank(filter_bank) {
+            warn!(
+                "{} may not have filter bank {filter_bank:?}",
+                self.model_name()
+            );
+        }
+    }
+
+    pub fn warn_if_constellation_unsupported(&self, constellation: Constellation) {
+        if !self.capabilities().supports_constellation(constellation) {
+            warn!("{} may not support {constellation:?}", self.model_name());
+        }
+    }
+
+    pub fn warn_if_external_reference_unsupported(&self) {
+        if !self.capabilities().external_reference {
+            warn!(
+                "{} may not have an external reference input",
+                self.model_name()
+            );
+        }
+    }
+
+    fn model_name(&self) -> &'static str {
+        self.model.map(|model| model.name()).unwrap_or("Unit")
+    }
+}
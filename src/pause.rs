@@ -0,0 +1,82 @@
+use anyhow::{ensure, Result};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::{MicrocontrollerPause, ZachtekDevice};
+
+// Slack for the unit's clock and for it to start talking again.
+const WAKE_MARGIN: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Pause {
+    until: Instant,
+    // Polling to restart when the unit wakes.
+    poll_sleep_interval: Option<Duration>,
+}
+
+impl ZachtekDevice {
+    /// Put the unit to sleep for `duration` (MPS). Polling stops
+    /// until it wakes, and `read_event` treats its silence as
+    /// expected rather than as a lost connection.
+    pub fn pause(&mut self, duration: Duration) -> Result<()> {
+        let seconds = duration.as_secs();
+        ensure!(
+            seconds <= MicrocontrollerPause::MAX_SECONDS as u64,
+            "Pause of {seconds} s is longer than {} s",
+            MicrocontrollerPause::MAX_SECONDS
+        );
+        let poll_sleep_interval = self.poll.as_ref().map(|poll| poll.poll_sleep_interval);
+        self.stop_poll_thread();
+        let args = format!("{seconds:07}");
+        // Anything still buffered was sent before the pause; read_event
+        // would take it as the unit waking.
+        let result = self
+            .set_verified_once(MicrocontrollerPause::CODE, args.as_bytes())
+            .and_then(|()| self.clear_input());
+        if let Err(err) = result {
+            if let Some(poll_sleep_interval) = poll_sleep_interval {
+                if let Err(poll_err) = self.start_poll_thread(poll_sleep_interval) {
+                    warn!("Failed to restart polling: {poll_err:#}");
+                }
+            }
+            return Err(err);
+        }
+        info!("Unit paused for {seconds} s");
+        self.pause = Some(Pause {
+            until: Instant::now() + duration + WAKE_MARGIN,
+            poll_sleep_interval,
+        });
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause.is_some()
+    }
+
+    /// Time left until the unit is expected back.
+    pub fn pause_remaining(&self) -> Option<Duration> {
+        let pause = self.pause.as_ref()?;
+        Some(pause.until.saturating_duration_since(Instant::now()))
+    }
+
+    // End the pause if it is over, or if `woke` says the unit is
+    // talking again. Returns whether it ended.
+    pub(crate) fn end_pause(&mut self, woke: bool) -> bool {
+        let Some(pause) = self.pause else {
+            return false;
+        };
+        if !woke && Instant::now() < pause.until {
+            return false;
+        }
+        self.pause = None;
+        info!("Unit pause over");
+        if let Some(poll_sleep_interval) = pause.poll_sleep_interval {
+            // A broken port shows up on the next read, and reconnecting
+            // restarts polling.
+            if let Err(err) = self.start_poll_thread(poll_sleep_interval) {
+                warn!("Failed to restart polling: {err:#}");
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,173 @@
+use anyhow::{bail, Result};
+use std::fmt;
+use std::ops::Range;
+
+use crate::{PowerData, PowerEncoding};
+
+/// Transmit power restricted to the levels WSPR can encode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Power {
+    dbm: u8,
+}
+
+impl Power {
+    // WSPR only carries power levels ending in 0, 3 or 7 dBm.
+    pub const LEGAL_DBM: &'static [u8] = &[
+        0, 3, 7, 10, 13, 17, 20, 23, 27, 30, 33, 37, 40, 43, 47, 50, 53, 57, 60,
+    ];
+
+    // In altitude mode the firmware reports altitude / 300 m in the
+    // power field, rounded down to a legal level.
+    pub const METERS_PER_DB: u32 = 300;
+
+    pub fn new(dbm: u8) -> Result<Self> {
+        if !Self::LEGAL_DBM.contains(&dbm) {
+            bail!("{dbm} dBm is not a legal WSPR power level");
+        }
+        Ok(Self { dbm })
+    }
+
+    /// Round down to the nearest legal level.
+    pub fn floor(dbm: u8) -> Self {
+        let dbm = Self::LEGAL_DBM
+            .iter()
+            .copied()
+            .take_while(|legal| *legal <= dbm)
+            .last()
+            .unwrap_or(0);
+        Self { dbm }
+    }
+
+    pub fn all() -> impl Iterator<Item = Power> {
+        Self::LEGAL_DBM.iter().map(|&dbm| Self { dbm })
+    }
+
+    pub fn dbm(&self) -> u8 {
+        self.dbm
+    }
+
+    pub fn watts(&self) -> f32 {
+        10f32.powf((self.dbm as f32 - 30.) / 10.)
+    }
+
+    /// Power level the firmware reports for a given altitude.
+    pub fn from_altitude(meters: u32) -> Self {
+        let dbm = (meters / Self::METERS_PER_DB).min(u8::MAX as u32) as u8;
+        Self::floor(dbm)
+    }
+
+    /// Altitudes (in meters) that are reported as this power level.
+    /// The top level is open ended.
+    pub fn altitude_range(&self) -> Range<u32> {
+        let start = self.dbm as u32 * Self::METERS_PER_DB;
+        let end = Self::LEGAL_DBM
+            .iter()
+            .find(|legal| **legal > self.dbm)
+            .map(|next| *next as u32 * Self::METERS_PER_DB)
+            .unwrap_or(u32::MAX);
+        start..end
+    }
+
+    /// What a remote receiver decodes from this power field.
+    pub fn report(&self, encoding: PowerEncoding) -> ReportedPower {
+        match encoding {
+            PowerEncoding::Normal => ReportedPower::Power(*self),
+            PowerEncoding::Altitude => ReportedPower::Altitude(self.altitude_range()),
+        }
+    }
+}
+
+impl TryFrom<u8> for Power {
+    type Error = anyhow::Error;
+
+    fn try_from(dbm: u8) -> Result<Self> {
+        Self::new(dbm)
+    }
+}
+
+impl TryFrom<&PowerData> for Power {
+    type Error = anyhow::Error;
+
+    fn try_from(data: &PowerData) -> Result<Self> {
+        Self::new(data.dbm)
+    }
+}
+
+impl fmt::Display for Power {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} dBm ({:.3} W)", self.dbm, self.watts())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReportedPower {
+    Power(Power),
+    Altitude(Range<u32>),
+}
+
+impl fmt::Display for ReportedPower {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportedPower::Power(power) => write!(f, "{power}"),
+            ReportedPower::Altitude(range) if range.end == u32::MAX => {
+                write!(f, ">= {} m", range.start)
+            }
+            ReportedPower::Altitude(range) => write!(f, "{}-{} m", range.start, range.end),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_legal_levels_are_accepted() {
+        assert_eq!(Power::new(23).unwrap().dbm(), 23);
+        assert!(Power::new(22).is_err());
+        assert!(Power::new(61).is_err());
+        assert_eq!(Power::all().count(), Power::LEGAL_DBM.len());
+    }
+
+    #[test]
+    fn floor_rounds_down_to_a_legal_level() {
+        assert_eq!(Power::floor(0).dbm(), 0);
+        assert_eq!(Power::floor(2).dbm(), 0);
+        assert_eq!(Power::floor(12).dbm(), 10);
+        assert_eq!(Power::floor(37).dbm(), 37);
+        assert_eq!(Power::floor(u8::MAX).dbm(), 60);
+    }
+
+    #[test]
+    fn watts() {
+        assert!((Power::new(30).unwrap().watts() - 1.).abs() < 1e-6);
+        assert!((Power::new(0).unwrap().watts() - 0.001).abs() < 1e-9);
+    }
+
+    #[test]
+    fn altitude_encoding() {
+        assert_eq!(Power::from_altitude(0).dbm(), 0);
+        assert_eq!(Power::from_altitude(2999).dbm(), 7);
+        assert_eq!(Power::from_altitude(3000).dbm(), 10);
+        assert_eq!(Power::from_altitude(u32::MAX).dbm(), 60);
+        assert_eq!(Power::new(10).unwrap().altitude_range(), 3000..3900);
+        assert_eq!(Power::new(60).unwrap().altitude_range(), 18_000..u32::MAX);
+        for power in Power::all() {
+            let range = power.altitude_range();
+            assert_eq!(Power::from_altitude(range.start), power);
+        }
+    }
+
+    #[test]
+    fn report_follows_encoding() {
+        let power = Power::new(10).unwrap();
+        assert_eq!(
+            power.report(PowerEncoding::Normal),
+            ReportedPower::Power(power)
+        );
+        assert_eq!(
+            power.report(PowerEncoding::Altitude),
+            ReportedPower::Altitude(3000..3900)
+        );
+    }
+}
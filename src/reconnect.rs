@@ -0,0 +1,252 @@
+use anyhow::{bail, Context, Result};
+use serialport::SerialPortType;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::{
+    is_port_error, is_timeout, open_port, DiscoveredDevice, Event, Response, ZachtekDevice,
+};
+
+/// Where a device's port came from.
+#[derive(Debug, Clone)]
+pub struct PortOrigin {
+    pub port_name: String,
+    // USB serial number, used to find the unit again if it comes back
+    // under a different port name.
+    pub serial_number: Option<String>,
+    // USB vendor and product ID, checked along with the serial number.
+    pub usb_id: Option<(u16, u16)>,
+    pub timeout: Duration,
+}
+
+impl PortOrigin {
+    pub fn new(port_name: &str, timeout: Duration) -> Self {
+        let usb = serialport::available_ports()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|port| port.port_name == port_name)
+            .and_then(|port| match port.port_type {
+                SerialPortType::UsbPort(usb) => Some(usb),
+                _ => None,
+            });
+        Self {
+            port_name: port_name.to_string(),
+            serial_number: usb.as_ref().and_then(|usb| usb.serial_number.clone()),
+            usb_id: usb.map(|usb| (usb.vid, usb.pid)),
+            timeout,
+        }
+    }
+
+    /// Origin of a unit found by `discover`, using the USB details
+    /// seen then rather than looking the port up again.
+    pub fn from_discovered(found: &DiscoveredDevice, timeout: Duration) -> Self {
+        Self {
+            port_name: found.port_name.clone(),
+            serial_number: found.serial_number().map(str::to_string),
+            usb_id: found.usb.as_ref().map(|usb| (usb.vid, usb.pid)),
+            timeout,
+        }
+    }
+
+    /// Current name of the port, looked up by serial number if known.
+    pub fn find(&self) -> Option<String> {
+        let ports = serialport::available_ports().ok()?;
+        let Some(serial_number) = &self.serial_number else {
+            return ports
+                .into_iter()
+                .find(|port| port.port_name == self.port_name)
+                .map(|port| port.port_name);
+        };
+        ports
+            .into_iter()
+            .find(|port| match &port.port_type {
+                SerialPortType::UsbPort(usb) => {
+                    usb.serial_number.as_ref() == Some(serial_number)
+                        && self
+                            .usb_id
+                            .is_none_or(|usb_id| usb_id == (usb.vid, usb.pid))
+                }
+                _ => false,
+            })
+            .map(|port| port.port_name)
+    }
+}
+
+// How often `reconnect_with` checks whether to give up.
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Exponential backoff between reconnect attempts.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    // Give up after this many attempts; retry forever if `None`.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+impl Backoff {
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.initial
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max)
+    }
+}
+
+impl ZachtekDevice {
+    /// Open `port_name` and set the unit running. Unlike
+    /// `connect`, the device remembers the port so it can reconnect.
+    pub fn open(port_name: &str, timeout: Duration) -> Result<Self> {
+        let origin = PortOrigin::new(port_name, timeout);
+        let mut device = Self::connect(open_port(port_name, timeout)?)?;
+        device.origin = Some(origin);
+        Ok(device)
+    }
+
+    /// Open the unit `origin` describes, under whatever port name it
+    /// has now.
+    pub fn open_origin(mut origin: PortOrigin) -> Result<Self> {
+        let Some(port_name) = origin.find() else {
+            bail!("{} is not present", origin.port_name);
+        };
+        let mut device = Self::connect(open_port(&port_name, origin.timeout)?)?;
+        origin.port_name = port_name;
+        device.origin = Some(origin);
+        Ok(device)
+    }
+
+    pub fn origin(&self) -> Option<&PortOrigin> {
+        self.origin.as_ref()
+    }
+
+    pub fn serial_number(&self) -> Option<&str> {
+        self.origin.as_ref()?.serial_number.as_deref()
+    }
+
+    /// Whether the port failed and the next `read_event` will
+    /// reconnect.
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected
+    }
+
+    pub fn set_backoff(&mut self, backoff: Backoff) {
+        self.backoff = backoff;
+    }
+
+    /// Reopen the port, waiting for the unit to reappear, then set it
+    /// running, re-read its identity if it had been identified and
+    /// restart polling.
+    pub fn reconnect(&mut self) -> Result<()> {
+        self.reconnect_with(|| false)
+    }
+
+    /// `reconnect`, calling `cancel` regularly while waiting between
+    /// attempts and giving up once it returns true.
+    pub fn reconnect_with(&mut self, mut cancel: impl FnMut() -> bool) -> Result<()> {
+        let origin = self
+            .origin
+            .clone()
+            .context("Can't reconnect a device not opened by name")?;
+        let poll_sleep_interval = self.poll.as_ref().map(|poll| poll.poll_sleep_interval);
+        self.stop_poll_thread();
+        let started = Instant::now();
+        let mut attempt = 0;
+        loop {
+            match self.try_reopen(&origin) {
+                Ok(()) => break,
+                Err(err) => {
+                    attempt += 1;
+                    if self
+                        .backoff
+                        .max_attempts
+                        .is_some_and(|max_attempts| attempt >= max_attempts)
+                    {
+                        return Err(err.context(format!("Gave up after {attempt} attempts")));
+                    }
+                    let delay = self.backoff.delay(attempt - 1);
+                    warn!("Reconnect attempt {attempt} failed: {err:#}; retrying in {delay:?}");
+                    let retry = Instant::now() + delay;
+                    loop {
+                        if cancel() {
+                            return Err(err.context("Reconnecting cancelled"));
+                        }
+                        let now = Instant::now();
+                        if now >= retry {
+                            break;
+                        }
+                        std::thread::sleep((retry - now).min(CANCEL_CHECK_INTERVAL));
+                    }
+                }
+            }
+        }
+        self.disconnected = false;
+        info!(
+            "Reconnected to {} after {:?}",
+            self.origin
+                .as_ref()
+                .map(|origin| origin.port_name.as_str())
+                .unwrap_or_default(),
+            started.elapsed()
+        );
+        if let Some(poll_sleep_interval) = poll_sleep_interval {
+            self.start_poll_thread(poll_sleep_interval)?;
+        }
+        Ok(())
+    }
+
+    fn try_reopen(&mut self, origin: &PortOrigin) -> Result<()> {
+        let Some(port_name) = origin.find() else {
+            bail!("{} is not present", origin.port_name);
+        };
+        self.port = open_port(&port_name, origin.timeout)?;
+        self.set_run()?;
+        self.clear_input()?;
+        // A paused unit won't answer; it keeps its earlier identity.
+        if self.info.is_some() && !self.is_paused() {
+            self.handshake()?;
+        }
+        if let Some(origin) = self.origin.as_mut() {
+            origin.port_name = port_name;
+        }
+        Ok(())
+    }
+
+    /// Read the next response, turning port failures into a
+    /// `Disconnected` event and reconnecting on the following call.
+    /// Returns `None` if the read timed out, which is expected while
+    /// the unit is paused.
+    pub fn read_event(&mut self) -> Result<Option<Event>> {
+        if self.disconnected {
+            self.reconnect()?;
+            return Ok(Some(Event::Reconnected(self.info().cloned())));
+        }
+        if self.end_pause(false) {
+            return Ok(Some(Event::Resumed));
+        }
+        Ok(match self.read_response() {
+            Ok(response) => {
+                // Anything but the echo of the pause itself means the
+                // unit is awake, e.g. it was reset.
+                if !matches!(response, Response::MicrocontrollerPause(_)) {
+                    self.end_pause(true);
+                }
+                Some(Event::Response(response))
+            }
+            Err(err) if is_timeout(&err) => None,
+            Err(err) if is_port_error(&err) => {
+                self.disconnected = true;
+                Some(Event::Disconnected(err.to_string()))
+            }
+            Err(err) => Some(Event::Error(err.to_string())),
+        })
+    }
+}
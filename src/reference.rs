@@ -0,0 +1,157 @@
+use chrono::{DateTime, Utc};
+use std::fmt;
+use tracing::{info, warn};
+
+use crate::{Reference, Response};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceChange {
+    pub time: DateTime<Utc>,
+    // `None` for the first report seen.
+    pub from: Option<Reference>,
+    pub to: Reference,
+    // Whether the unit is meant to be on an external reference.
+    pub external_configured: bool,
+    // Configured external frequency (DER) at the time, if known.
+    pub external_hertz: Option<u32>,
+}
+
+impl ReferenceChange {
+    /// On the internal reference while an external one is configured,
+    /// either from the first report or after losing the external one.
+    pub fn is_fallback(&self) -> bool {
+        self.external_configured
+            && self.to == Reference::Internal
+            && self.from != Some(Reference::Internal)
+    }
+}
+
+impl fmt::Display for ReferenceChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.from {
+            Some(from) => write!(f, "{}: {from:?} -> {:?}", self.time, self.to),
+            None => write!(f, "{}: {:?}", self.time, self.to),
+        }
+    }
+}
+
+/// Follows the reference the unit is locked to (CCR) and its
+/// configured external frequency (DER).
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceMonitor {
+    // DER always holds a frequency, so whether an external reference
+    // is actually connected has to be configured here.
+    pub external_configured: bool,
+    pub current: Option<Reference>,
+    pub external_hertz: Option<u32>,
+    pub changes: Vec<ReferenceChange>,
+}
+
+impl ReferenceMonitor {
+    pub fn new(external_configured: bool) -> Self {
+        Self {
+            external_configured,
+            ..Self::default()
+        }
+    }
+
+    /// Feed a response received at `time`. Returns the change, if this
+    /// response is a reference report that differs from the last one.
+    pub fn check(&mut self, response: &Response, time: DateTime<Utc>) -> Option<ReferenceChange> {
+        let reference = match response {
+            Response::ExternalReferenceFrequencyData(data) => {
+                self.external_hertz = Some(data.hertz);
+                return None;
+            }
+            Response::CurrentReferenceCommand(command) => command.reference,
+            _ => return None,
+        };
+        if self.current == Some(reference) {
+            return None;
+        }
+        let change = ReferenceChange {
+            time,
+            from: self.current,
+            to: reference,
+            external_configured: self.external_configured,
+            external_hertz: self.external_hertz,
+        };
+        self.current = Some(reference);
+        if change.is_fallback() {
+            warn!(
+                "Unit is on its internal reference at {time} with an external reference configured"
+            );
+        } else {
+            info!("Reference {change}");
+        }
+        self.changes.push(change.clone());
+        Some(change)
+    }
+
+    pub fn fallbacks(&self) -> impl Iterator<Item = &ReferenceChange> {
+        self.changes.iter().filter(|change| change.is_fallback())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CurrentReferenceCommand, ExternalReferenceFrequencyData};
+    use chrono::TimeZone;
+
+    fn reference(reference: Reference) -> Response {
+        Response::CurrentReferenceCommand(CurrentReferenceCommand { reference })
+    }
+
+    fn at(second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, second).unwrap()
+    }
+
+    #[test]
+    fn losing_the_external_reference_is_a_fallback() {
+        let mut monitor = ReferenceMonitor::new(true);
+        assert!(!monitor
+            .check(&reference(Reference::External), at(0))
+            .unwrap()
+            .is_fallback());
+        assert!(monitor
+            .check(&reference(Reference::Internal), at(1))
+            .unwrap()
+            .is_fallback());
+        assert_eq!(monitor.fallbacks().count(), 1);
+    }
+
+    #[test]
+    fn starting_on_internal_is_a_fallback() {
+        let mut monitor = ReferenceMonitor::new(true);
+        let change = monitor
+            .check(&reference(Reference::Internal), at(0))
+            .unwrap();
+        assert_eq!(change.from, None);
+        assert!(change.is_fallback());
+    }
+
+    #[test]
+    fn internal_is_fine_without_an_external_reference() {
+        let mut monitor = ReferenceMonitor::new(false);
+        monitor.check(
+            &Response::ExternalReferenceFrequencyData(ExternalReferenceFrequencyData {
+                hertz: 10_000_000,
+            }),
+            at(0),
+        );
+        monitor.check(&reference(Reference::External), at(1));
+        monitor.check(&reference(Reference::Internal), at(2));
+        assert_eq!(monitor.external_hertz, Some(10_000_000));
+        assert_eq!(monitor.changes.len(), 2);
+        assert_eq!(monitor.fallbacks().count(), 0);
+    }
+
+    #[test]
+    fn repeated_reports_are_not_changes() {
+        let mut monitor = ReferenceMonitor::new(true);
+        monitor.check(&reference(Reference::Internal), at(0));
+        assert_eq!(monitor.check(&reference(Reference::Internal), at(1)), None);
+        assert_eq!(monitor.fallbacks().count(), 1);
+    }
+}
@@ -0,0 +1,219 @@
+use chrono::{DateTime, Duration as ChronoDuration, DurationRound, Timelike, Utc};
+use std::time::Duration;
+
+use crate::{coordinated_band, Band, TimeSlot};
+
+// WSPR transmissions start one second into an even minute and last
+// 162 symbols of 8192/12000 s, about 110.6 s.
+pub const WSPR_SLOT: Duration = Duration::from_secs(120);
+pub const WSPR_START_OFFSET: Duration = Duration::from_secs(1);
+pub const WSPR_TX_DURATION: Duration = Duration::from_millis(110_592);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlannedTransmission {
+    pub start: DateTime<Utc>,
+    pub band: Band,
+}
+
+impl PlannedTransmission {
+    pub fn end(&self) -> DateTime<Utc> {
+        self.start + chrono_duration(WSPR_TX_DURATION)
+    }
+}
+
+/// Predicts when and on which band a unit transmits, from its time
+/// slot, enabled bands and TX pause. The unit cycles through the
+/// enabled bands in band order, one per matching slot, and sleeps for
+/// the TX pause after each complete cycle.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    pub time_slot: TimeSlot,
+    pub bands: Vec<Band>,
+    pub tx_pause: Duration,
+}
+
+impl Schedule {
+    /// `bands` are the enabled bands, in any order.
+    pub fn new(
+        time_slot: TimeSlot,
+        bands: impl IntoIterator<Item = Band>,
+        tx_pause: Duration,
+    ) -> Self {
+        let mut bands: Vec<Band> = bands.into_iter().collect();
+        bands.sort_by_key(|band| u8::from(*band));
+        bands.dedup();
+        Self {
+            time_slot,
+            bands,
+            tx_pause,
+        }
+    }
+
+    /// Predict the next `count` transmissions starting at or after
+    /// `now`. `last_band` is the most recently observed band (TBN), if
+    /// any, so the band cycle can be continued where the unit is.
+    pub fn next_transmissions(
+        &self,
+        now: DateTime<Utc>,
+        last_band: Option<Band>,
+        count: usize,
+    ) -> Vec<PlannedTransmission> {
+        let mut planned = Vec::with_capacity(count);
+        if self.bands.is_empty() {
+            return planned;
+        }
+
+        let mut cursor = last_band.and_then(|last| self.bands.iter().position(|b| *b == last));
+        let mut slot = first_slot_at_or_after(now - chrono_duration(WSPR_START_OFFSET));
+        // Bound the search so a schedule with no usable slots terminates.
+        let mut remaining_slots = 30 * 24 * 60 / 2;
+        while planned.len() < count && remaining_slots > 0 {
+            remaining_slots -= 1;
+            let Some(index) = self.band_index_for_slot(slot, cursor) else {
+                slot += chrono_duration(WSPR_SLOT);
+                continue;
+            };
+            planned.push(PlannedTransmission {
+                start: slot + chrono_duration(WSPR_START_OFFSET),
+                band: self.bands[index],
+            });
+            cursor = Some(index);
+            slot += chrono_duration(WSPR_SLOT);
+            if index == self.bands.len() - 1 && !self.tx_pause.is_zero() {
+                slot = first_slot_at_or_after(slot + chrono_duration(self.tx_pause));
+            }
+        }
+        planned
+    }
+
+    fn band_index_for_slot(&self, slot: DateTime<Utc>, cursor: Option<usize>) -> Option<usize> {
+        let minute = slot.minute();
+        let next = cursor.map(|i| (i + 1) % self.bands.len()).unwrap_or(0);
+        match self.time_slot {
+            TimeSlot::TenMinute(n) if minute % 10 == 2 * n as u32 => Some(next),
+            TimeSlot::TwentyMinute(n) if minute % 20 == 2 * n as u32 => Some(next),
+            TimeSlot::NoSchedule => Some(next),
+            TimeSlot::Tracker if minute == 0 => Some(next),
+            TimeSlot::BandCoordinated => {
+                let band = coordinated_band(slot)?;
+                self.bands.iter().position(|b| *b == band)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn first_slot_at_or_after(time: DateTime<Utc>) -> DateTime<Utc> {
+    let slot = chrono_duration(WSPR_SLOT);
+    let rounded = time
+        .duration_trunc(slot)
+        .expect("Failed to round time to slot.");
+    if rounded == time {
+        rounded
+    } else {
+        rounded + slot
+    }
+}
+
+fn chrono_duration(duration: Duration) -> ChronoDuration {
+    ChronoDuration::from_std(duration).expect("Duration out of range.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(minute: u32, second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, minute, second).unwrap()
+    }
+
+    fn planned(
+        schedule: &Schedule,
+        now: DateTime<Utc>,
+        last_band: Option<Band>,
+    ) -> Vec<(DateTime<Utc>, Band)> {
+        schedule
+            .next_transmissions(now, last_band, 3)
+            .into_iter()
+            .map(|planned| (planned.start, planned.band))
+            .collect()
+    }
+
+    #[test]
+    fn bands_are_sorted_and_deduplicated() {
+        let schedule = Schedule::new(
+            TimeSlot::NoSchedule,
+            [Band::B20m, Band::B40m, Band::B20m],
+            Duration::ZERO,
+        );
+        assert_eq!(schedule.bands, [Band::B40m, Band::B20m]);
+    }
+
+    #[test]
+    fn ten_minute_slot_cycles_through_bands() {
+        let schedule = Schedule::new(
+            TimeSlot::TenMinute(1),
+            [Band::B20m, Band::B40m],
+            Duration::ZERO,
+        );
+        assert_eq!(
+            planned(&schedule, at(0, 0), None),
+            [
+                (at(2, 1), Band::B40m),
+                (at(12, 1), Band::B20m),
+                (at(22, 1), Band::B40m)
+            ]
+        );
+        // Continues after the last band seen.
+        assert_eq!(
+            planned(&schedule, at(0, 0), Some(Band::B40m))[0],
+            (at(2, 1), Band::B20m)
+        );
+        // A transmission just starting is still next.
+        assert_eq!(
+            planned(&schedule, at(2, 1), None)[0],
+            (at(2, 1), Band::B40m)
+        );
+    }
+
+    #[test]
+    fn tx_pause_follows_each_cycle() {
+        let schedule = Schedule::new(
+            TimeSlot::TenMinute(1),
+            [Band::B20m, Band::B40m],
+            Duration::from_secs(30 * 60),
+        );
+        assert_eq!(
+            planned(&schedule, at(0, 0), None),
+            [
+                (at(2, 1), Band::B40m),
+                (at(12, 1), Band::B20m),
+                (at(52, 1), Band::B40m)
+            ]
+        );
+    }
+
+    #[test]
+    fn band_coordinated_uses_the_hopping_table() {
+        let schedule = Schedule::new(
+            TimeSlot::BandCoordinated,
+            [Band::B20m, Band::B40m],
+            Duration::ZERO,
+        );
+        assert_eq!(
+            planned(&schedule, at(0, 0), None),
+            [
+                (at(6, 1), Band::B40m),
+                (at(10, 1), Band::B20m),
+                (at(26, 1), Band::B40m)
+            ]
+        );
+    }
+
+    #[test]
+    fn no_bands_plans_nothing() {
+        let schedule = Schedule::new(TimeSlot::NoSchedule, [], Duration::ZERO);
+        assert!(planned(&schedule, at(0, 0), None).is_empty());
+    }
+}
@@ -0,0 +1,366 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+use crate::{
+    LocationSource, LocatorPrecision, LocatorPrecisionOption, Power, PowerData, PowerEncoding,
+    PowerEncodingOption, PrefixData, PrefixSuffix, PrefixSuffixOption, Response, SuffixData,
+    TransmissionRecord, ZachtekDevice,
+};
+
+/// What the unit puts in its messages.
+#[derive(Debug, Clone)]
+pub struct Station {
+    pub call_sign: String,
+    pub prefix_suffix: PrefixSuffix,
+    pub prefix: String,
+    // Suffix code: 0-9 for a digit, 10-35 for A-Z.
+    pub suffix: u8,
+    pub location_source: LocationSource,
+    pub locator_precision: LocatorPrecision,
+    // Manual locator (DL6, or DL4 if that is all there is).
+    pub locator: String,
+    pub power: Power,
+    pub power_encoding: PowerEncoding,
+    // The unit doesn't report its altitude; needed in altitude mode.
+    pub altitude_meters: Option<u32>,
+}
+
+/// WSPR message type. A plain call and a 4 character locator fit in
+/// type 1. Anything longer is sent as two alternating messages: type 2
+/// carries a compound (prefix or suffix) call and no locator, type 3 a
+/// hash of the call, shown as "<CALL>", and the 6 character locator.
+/// Receivers that haven't decoded the call yet can't resolve type 3,
+/// so spots of the same unit may show several forms of its call; join
+/// them by `base_call`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum MessageType {
+    Type1,
+    Type2,
+    Type3,
+}
+
+impl Station {
+    /// Call sign as decoded by receivers, e.g. "PA/K1ABC" or "K1ABC/7".
+    pub fn call(&self) -> String {
+        match self.prefix_suffix {
+            PrefixSuffix::Prefix => format!("{}/{}", self.prefix.trim(), self.call_sign),
+            PrefixSuffix::Suffix => {
+                let suffix = match self.suffix {
+                    0..=9 => (b'0' + self.suffix) as char,
+                    10..=35 => (b'A' + self.suffix - 10) as char,
+                    _ => '?',
+                };
+                format!("{}/{suffix}", self.call_sign)
+            }
+            PrefixSuffix::None => self.call_sign.clone(),
+        }
+    }
+
+    /// Messages the unit alternates between, see `MessageType`.
+    pub fn message_types(&self) -> &'static [MessageType] {
+        match (self.prefix_suffix, self.locator_precision) {
+            (PrefixSuffix::Prefix | PrefixSuffix::Suffix, _) => {
+                &[MessageType::Type2, MessageType::Type3]
+            }
+            (PrefixSuffix::None, LocatorPrecision::Maidenhead6) => {
+                &[MessageType::Type1, MessageType::Type3]
+            }
+            (PrefixSuffix::None, LocatorPrecision::Maidenhead4) => &[MessageType::Type1],
+        }
+    }
+
+    /// Call sign as a receiver shows it in `message_type`.
+    pub fn call_in(&self, message_type: MessageType) -> String {
+        match message_type {
+            MessageType::Type1 | MessageType::Type2 => self.call(),
+            MessageType::Type3 => format!("<{}>", self.call()),
+        }
+    }
+
+    /// Locator sent for a transmission, cut to the configured
+    /// precision. GPS units use the locator at the time if known.
+    pub fn grid(&self, record: &TransmissionRecord) -> String {
+        let locator = match (self.location_source, &record.locator) {
+            (LocationSource::Gps, Some(locator)) => locator,
+            _ => &self.locator,
+        };
+        let length = match self.locator_precision {
+            LocatorPrecision::Maidenhead4 => 4,
+            LocatorPrecision::Maidenhead6 => 6,
+        };
+        locator.chars().take(length).collect()
+    }
+
+    /// Locator as sent in `message_type`; type 2 has none.
+    pub fn grid_in(&self, message_type: MessageType, record: &TransmissionRecord) -> String {
+        let grid = self.grid(record);
+        match message_type {
+            MessageType::Type1 => grid.chars().take(4).collect(),
+            MessageType::Type2 => String::new(),
+            MessageType::Type3 => grid,
+        }
+    }
+
+    /// Power field sent. In altitude mode this encodes the altitude,
+    /// so is `None` if that isn't known.
+    pub fn dbm(&self) -> Option<u8> {
+        match self.power_encoding {
+            PowerEncoding::Normal => Some(self.power.dbm()),
+            PowerEncoding::Altitude => self
+                .altitude_meters
+                .map(|meters| Power::from_altitude(meters).dbm()),
+        }
+    }
+}
+
+/// A transmission as a spot would show it: in the WSPRnet/wsprd
+/// layout of date, time, frequency, call, grid and dBm.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpectedSpot {
+    // Start of the 2 minute slot.
+    pub slot: DateTime<Utc>,
+    pub frequency_hertz: f64,
+    pub call: String,
+    pub grid: String,
+    pub dbm: Option<u8>,
+    pub message_type: MessageType,
+}
+
+impl ExpectedSpot {
+    /// The spot for each message the transmission may have been, as
+    /// the unit doesn't report which one it sent. Empty if the record
+    /// has no frequency, e.g. the unit was reset before reporting it.
+    pub fn alternatives(station: &Station, record: &TransmissionRecord) -> Vec<Self> {
+        station
+            .message_types()
+            .iter()
+            .filter_map(|message_type| Self::new(station, record, *message_type))
+            .collect()
+    }
+
+    /// `None` if the record has no frequency.
+    pub fn new(
+        station: &Station,
+        record: &TransmissionRecord,
+        message_type: MessageType,
+    ) -> Option<Self> {
+        let frequency_hertz = record.frequency_hertz.or_else(|| {
+            record
+                .band
+                .and_then(|band| band.wspr_frequency())
+                .map(|hertz| hertz as f64)
+        })?;
+        let slot = record
+            .start
+            .duration_trunc(TimeDelta::minutes(2))
+            .unwrap_or(record.start);
+        Some(Self {
+            slot,
+            frequency_hertz,
+            call: station.call_in(message_type),
+            grid: station.grid_in(message_type, record),
+            dbm: station.dbm(),
+            message_type,
+        })
+    }
+
+    // wsprd writes dates as yymmdd and times as hhmm.
+    pub fn date(&self) -> String {
+        self.slot.format("%y%m%d").to_string()
+    }
+
+    pub fn time(&self) -> String {
+        self.slot.format("%H%M").to_string()
+    }
+
+    pub fn frequency_mhz(&self) -> f64 {
+        self.frequency_hertz / 1e6
+    }
+}
+
+impl fmt::Display for ExpectedSpot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {:>11.6} {:<12} {:<6} {:>2}",
+            self.date(),
+            self.time(),
+            self.frequency_mhz(),
+            self.call,
+            self.grid,
+            self.dbm.map(|dbm| dbm.to_string()).unwrap_or("-".into())
+        )
+    }
+}
+
+// Flattened to the same columns as the text layout, plus the message
+// type.
+impl Serialize for ExpectedSpot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut spot = serializer.serialize_struct("ExpectedSpot", 7)?;
+        spot.serialize_field("date", &self.date())?;
+        spot.serialize_field("time", &self.time())?;
+        spot.serialize_field("frequency", &format!("{:.6}", self.frequency_mhz()))?;
+        spot.serialize_field("call", &self.call)?;
+        spot.serialize_field("grid", &self.grid)?;
+        spot.serialize_field("dbm", &self.dbm)?;
+        spot.serialize_field("type", &self.message_type)?;
+        spot.end()
+    }
+}
+
+impl ZachtekDevice {
+    /// Read the settings that make up the unit's messages.
+    pub fn read_station(&mut self) -> Result<Station> {
+        let call_sign = self.read_call_sign()?;
+        let prefix_suffix =
+            self.query(PrefixSuffixOption::CODE, b"", |response| match response {
+                Response::PrefixSuffixOption(option) => Some(option.prefix_suffix),
+                _ => None,
+            })?;
+        let prefix = self.query(PrefixData::CODE, b"", |response| match response {
+            Response::PrefixData(data) => Some(data.data_prefix.clone()),
+            _ => None,
+        })?;
+        let suffix = self.query(SuffixData::CODE, b"", |response| match response {
+            Response::SuffixData(data) => Some(data.data_suffix.clone()),
+            _ => None,
+        })?;
+        let suffix = suffix
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid suffix code '{suffix}'"))?;
+        let location_source = self.read_location_source()?;
+        let locator_precision =
+            self.query(
+                LocatorPrecisionOption::CODE,
+                b"",
+                |response| match response {
+                    Response::LocatorPrecisionOption(option) => Some(option.locator_precision),
+                    _ => None,
+                },
+            )?;
+        let locator = self.read_manual_locator()?;
+        let power = self.query(PowerData::CODE, b"", |response| match response {
+            Response::PowerData(data) => Some(Power::floor(data.dbm)),
+            _ => None,
+        })?;
+        let power_encoding =
+            self.query(PowerEncodingOption::CODE, b"", |response| match response {
+                Response::PowerEncodingOption(option) => Some(option.power_encoding),
+                _ => None,
+            })?;
+        Ok(Station {
+            call_sign,
+            prefix_suffix,
+            prefix,
+            suffix,
+            location_source,
+            locator_precision,
+            locator,
+            power,
+            power_encoding,
+            altitude_meters: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn station(prefix_suffix: PrefixSuffix, locator_precision: LocatorPrecision) -> Station {
+        Station {
+            call_sign: "K1ABC".into(),
+            prefix_suffix,
+            prefix: " PA".into(),
+            suffix: 7,
+            location_source: LocationSource::Manual,
+            locator_precision,
+            locator: "FN42hn".into(),
+            power: Power::new(23).unwrap(),
+            power_encoding: PowerEncoding::Normal,
+            altitude_meters: None,
+        }
+    }
+
+    fn record(locator: Option<&str>) -> TransmissionRecord {
+        TransmissionRecord {
+            start: Utc.with_ymd_and_hms(2024, 1, 1, 0, 3, 1).unwrap(),
+            end: None,
+            band: Some(crate::Band::B20m),
+            frequency_hertz: None,
+            filter_bank: None,
+            symbols: None,
+            gps_locked: None,
+            locator: locator.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn call_includes_prefix_or_suffix() {
+        let mut station = station(PrefixSuffix::None, LocatorPrecision::Maidenhead4);
+        assert_eq!(station.call(), "K1ABC");
+        station.prefix_suffix = PrefixSuffix::Prefix;
+        assert_eq!(station.call(), "PA/K1ABC");
+        station.prefix_suffix = PrefixSuffix::Suffix;
+        assert_eq!(station.call(), "K1ABC/7");
+        station.suffix = 10;
+        assert_eq!(station.call(), "K1ABC/A");
+    }
+
+    #[test]
+    fn grid_follows_precision_and_source() {
+        let mut station = station(PrefixSuffix::None, LocatorPrecision::Maidenhead6);
+        assert_eq!(station.grid(&record(Some("JO65ab"))), "FN42hn");
+        station.location_source = LocationSource::Gps;
+        assert_eq!(station.grid(&record(Some("JO65ab"))), "JO65ab");
+        assert_eq!(station.grid(&record(None)), "FN42hn");
+        station.locator_precision = LocatorPrecision::Maidenhead4;
+        assert_eq!(station.grid(&record(Some("JO65ab"))), "JO65");
+    }
+
+    #[test]
+    fn plain_call_with_4_character_locator_is_type_1() {
+        let station = station(PrefixSuffix::None, LocatorPrecision::Maidenhead4);
+        let spots = ExpectedSpot::alternatives(&station, &record(None));
+        assert_eq!(spots.len(), 1);
+        assert_eq!(spots[0].message_type, MessageType::Type1);
+        assert_eq!(spots[0].call, "K1ABC");
+        assert_eq!(spots[0].grid, "FN42");
+        assert_eq!(spots[0].frequency_hertz, 14_097_100.);
+        assert_eq!(spots[0].time(), "0002");
+    }
+
+    #[test]
+    fn six_character_locator_alternates_with_type_3() {
+        let station = station(PrefixSuffix::None, LocatorPrecision::Maidenhead6);
+        let spots = ExpectedSpot::alternatives(&station, &record(None));
+        let forms: Vec<(&str, &str)> = spots
+            .iter()
+            .map(|spot| (spot.call.as_str(), spot.grid.as_str()))
+            .collect();
+        assert_eq!(forms, vec![("K1ABC", "FN42"), ("<K1ABC>", "FN42hn")]);
+    }
+
+    #[test]
+    fn compound_call_alternates_type_2_and_3() {
+        let station = station(PrefixSuffix::Prefix, LocatorPrecision::Maidenhead4);
+        let spots = ExpectedSpot::alternatives(&station, &record(None));
+        let forms: Vec<(MessageType, &str, &str)> = spots
+            .iter()
+            .map(|spot| (spot.message_type, spot.call.as_str(), spot.grid.as_str()))
+            .collect();
+        assert_eq!(
+            forms,
+            vec![
+                (MessageType::Type2, "PA/K1ABC", ""),
+                (MessageType::Type3, "<PA/K1ABC>", "FN42"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Timelike, Utc};
+use std::fmt;
+use std::io::Write;
+use tracing::{debug, info};
+
+use crate::{LatLon, Response, TimeSlot, TimeSlotOption, TransmissionRecord, ZachtekDevice};
+
+/// A GPS locator (GL6) and when the unit first reported it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackPoint {
+    pub time: DateTime<Utc>,
+    pub locator: String,
+    // Centre of the locator's subsquare.
+    pub position: LatLon,
+}
+
+/// The unit moved from one subsquare to another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movement {
+    pub time: DateTime<Utc>,
+    pub from: String,
+    pub to: String,
+    // Between subsquare centres, so only good to a few kilometres.
+    pub distance_km: f64,
+}
+
+impl Movement {
+    /// Whether the move crossed into another 4 character square.
+    pub fn changed_square(&self) -> bool {
+        !self.from[..4].eq_ignore_ascii_case(&self.to[..4])
+    }
+}
+
+impl fmt::Display for Movement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: moved {} -> {} ({:.1} km)",
+            self.time, self.from, self.to, self.distance_km
+        )
+    }
+}
+
+/// Position history from the GPS locator (GL6). A point is recorded
+/// each time the locator changes.
+#[derive(Debug, Clone, Default)]
+pub struct PositionTrack {
+    pub points: Vec<TrackPoint>,
+    pub movements: Vec<Movement>,
+}
+
+impl PositionTrack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a response received at `time`. Returns the movement, if
+    /// this response is a locator in a different subsquare.
+    pub fn check(&mut self, response: &Response, time: DateTime<Utc>) -> Option<Movement> {
+        let Response::Locator6GPS(locator) = response else {
+            return None;
+        };
+        // Empty until the GPS has a fix.
+        let locator = locator.maidenhead_6.trim();
+        if self
+            .points
+            .last()
+            .is_some_and(|last| last.locator.eq_ignore_ascii_case(locator))
+        {
+            return None;
+        }
+        let position = match LatLon::from_locator(locator) {
+            Ok(position) if locator.len() == 6 => position,
+            _ => {
+                debug!("Ignoring GPS locator '{locator}'");
+                return None;
+            }
+        };
+        let point = TrackPoint {
+            time,
+            locator: locator.to_string(),
+            position,
+        };
+        let movement = self.points.last().map(|last| Movement {
+            time,
+            from: last.locator.clone(),
+            to: point.locator.clone(),
+            distance_km: last.position.distance_km(&point.position),
+        });
+        self.points.push(point);
+        if let Some(movement) = &movement {
+            info!("{movement}");
+            self.movements.push(movement.clone());
+        }
+        movement
+    }
+
+    /// Why a tracker unit sent `record`: the latest movement after
+    /// `since` (the previous transmission) and up to its start, or
+    /// else the top of the hour.
+    pub fn trigger(
+        &self,
+        record: &TransmissionRecord,
+        since: Option<DateTime<Utc>>,
+    ) -> TrackerTrigger {
+        let movement = self
+            .movements
+            .iter()
+            .rev()
+            .skip_while(|movement| movement.time > record.start)
+            .take_while(|movement| since.is_none_or(|since| movement.time > since))
+            .next();
+        match movement {
+            Some(movement) => TrackerTrigger::Movement(movement.clone()),
+            None if record.start.minute() == 0 => TrackerTrigger::TopOfHour,
+            None => TrackerTrigger::Unexplained,
+        }
+    }
+
+    /// Match each transmission, in time order, with its trigger.
+    pub fn correlate(&self, transmissions: &[TransmissionRecord]) -> Vec<TrackerTransmission> {
+        let mut since = None;
+        transmissions
+            .iter()
+            .map(|record| {
+                let trigger = self.trigger(record, since);
+                since = Some(record.start);
+                TrackerTransmission {
+                    record: record.clone(),
+                    trigger,
+                }
+            })
+            .collect()
+    }
+
+    /// Write the track as a GPX 1.1 document, one track point per
+    /// locator named after it.
+    pub fn write_gpx<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            writer,
+            r#"<gpx version="1.1" creator="zachtek" xmlns="http://www.topografix.com/GPX/1/1">"#
+        )?;
+        writeln!(writer, "  <trk>")?;
+        writeln!(writer, "    <trkseg>")?;
+        // Locators are checked to be letters and digits, so need no
+        // escaping.
+        for point in &self.points {
+            writeln!(
+                writer,
+                r#"      <trkpt lat="{:.6}" lon="{:.6}"><time>{}</time><name>{}</name></trkpt>"#,
+                point.position.lat,
+                point.position.lon,
+                point.time.to_rfc3339_opts(SecondsFormat::Secs, true),
+                point.locator
+            )?;
+        }
+        writeln!(writer, "    </trkseg>")?;
+        writeln!(writer, "  </trk>")?;
+        writeln!(writer, "</gpx>")?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackerTrigger {
+    Movement(Movement),
+    // Tracker units also send once an hour while standing still.
+    TopOfHour,
+    // Neither; e.g. moved before tracking started, or not in tracker
+    // mode.
+    Unexplained,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrackerTransmission {
+    pub record: TransmissionRecord,
+    pub trigger: TrackerTrigger,
+}
+
+impl fmt::Display for TrackerTransmission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:>5}: ",
+            self.record.start.format("%Y-%m-%d %H:%M"),
+            self.record.band.map(|band| band.name()).unwrap_or("?")
+        )?;
+        match &self.trigger {
+            TrackerTrigger::Movement(movement) => write!(
+                f,
+                "moved {} -> {} ({:.1} km) at {}",
+                movement.from,
+                movement.to,
+                movement.distance_km,
+                movement.time.format("%H:%M:%S")
+            ),
+            TrackerTrigger::TopOfHour => write!(f, "top of hour"),
+            TrackerTrigger::Unexplained => write!(f, "no movement seen"),
+        }
+    }
+}
+
+impl ZachtekDevice {
+    pub fn read_time_slot(&mut self) -> Result<TimeSlot> {
+        self.query(TimeSlotOption::CODE, b"", |response| match response {
+            Response::TimeSlotOption(option) => Some(option.time_slot),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn locator(locator: &str) -> Response {
+        Response::Locator6GPS(crate::Locator6GPS {
+            maidenhead_6: locator.into(),
+        })
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, hour, minute, 0).unwrap()
+    }
+
+    fn record(start: DateTime<Utc>) -> TransmissionRecord {
+        TransmissionRecord {
+            start,
+            end: None,
+            band: None,
+            frequency_hertz: None,
+            filter_bank: None,
+            symbols: None,
+            gps_locked: None,
+            locator: None,
+        }
+    }
+
+    fn track() -> PositionTrack {
+        let mut track = PositionTrack::new();
+        track.check(&locator("      "), at(0, 1));
+        assert_eq!(track.check(&locator("JO65ab"), at(0, 5)), None);
+        assert_eq!(track.check(&locator("jo65AB"), at(0, 6)), None);
+        track.check(&locator("JO65"), at(0, 7));
+        let movement = track.check(&locator("JO65ac"), at(0, 10)).unwrap();
+        assert_eq!(movement.from, "JO65ab");
+        assert!(!movement.changed_square());
+        assert!(movement.distance_km > 4. && movement.distance_km < 5.);
+        track
+    }
+
+    #[test]
+    fn points_are_recorded_on_change() {
+        let track = track();
+        assert_eq!(track.points.len(), 2);
+        assert_eq!(track.movements.len(), 1);
+    }
+
+    #[test]
+    fn transmissions_are_matched_to_triggers() {
+        let track = track();
+        let transmissions = [record(at(0, 12)), record(at(0, 30)), record(at(1, 0))];
+        let triggers: Vec<TrackerTrigger> = track
+            .correlate(&transmissions)
+            .into_iter()
+            .map(|transmission| transmission.trigger)
+            .collect();
+        assert_eq!(
+            triggers,
+            vec![
+                TrackerTrigger::Movement(track.movements[0].clone()),
+                TrackerTrigger::Unexplained,
+                TrackerTrigger::TopOfHour,
+            ]
+        );
+    }
+
+    #[test]
+    fn movement_after_start_is_not_a_trigger() {
+        let track = track();
+        assert_eq!(
+            track.trigger(&record(at(0, 8)), None),
+            TrackerTrigger::Unexplained
+        );
+    }
+
+    #[test]
+    fn gpx_has_a_point_per_locator() {
+        let mut gpx = Vec::new();
+        track().write_gpx(&mut gpx).unwrap();
+        let gpx = String::from_utf8(gpx).unwrap();
+        assert!(gpx.starts_with("<?xml"));
+        assert_eq!(gpx.matches("<trkpt ").count(), 2);
+        assert!(gpx.contains("<time>2024-01-01T00:05:00Z</time><name>JO65ab</name>"));
+        assert!(gpx.trim_end().ends_with("</gpx>"));
+    }
+}
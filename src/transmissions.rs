@@ -0,0 +1,319 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{Band, FilterBank, GpsLock, Response, TransmitterWSPRSymbol};
+
+/// Everything known about one transmission.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransmissionRecord {
+    pub start: DateTime<Utc>,
+    // `None` if the transmission was cut short, e.g. by a reset.
+    pub end: Option<DateTime<Utc>>,
+    pub band: Option<Band>,
+    pub frequency_hertz: Option<f64>,
+    pub filter_bank: Option<FilterBank>,
+    // Symbols sent, from the last TWS; 162 for a complete message.
+    pub symbols: Option<u8>,
+    pub gps_locked: Option<bool>,
+    pub locator: Option<String>,
+}
+
+impl TransmissionRecord {
+    pub fn is_complete(&self) -> bool {
+        self.end.is_some() && self.symbols == Some(TransmitterWSPRSymbol::SYMBOLS)
+    }
+}
+
+/// Builds a `TransmissionRecord` per transmission from the response
+/// stream. Band, frequency and filter are taken from the latest
+/// reports before or during the transmission, GPS state from the
+/// latest reports before it starts.
+#[derive(Debug, Clone, Default)]
+pub struct TransmissionRecorder {
+    band: Option<Band>,
+    frequency_hertz: Option<f64>,
+    filter_bank: Option<FilterBank>,
+    gps_locked: Option<bool>,
+    locator_4: Option<String>,
+    locator_6: Option<String>,
+    current: Option<TransmissionRecord>,
+}
+
+impl TransmissionRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a response received at `time`. Returns the finished
+    /// record when a transmission ends.
+    pub fn update(
+        &mut self,
+        response: &Response,
+        time: DateTime<Utc>,
+    ) -> Option<TransmissionRecord> {
+        match response {
+            Response::TransmitterStatus(status) if status.on => {
+                // A second TON T without a TON F in between means the
+                // first one never finished.
+                let unfinished = self.current.take();
+                self.current = Some(self.start(time));
+                return unfinished;
+            }
+            Response::TransmitterStatus(_) => {
+                let mut record = self.current.take()?;
+                record.end = Some(time);
+                return Some(record);
+            }
+            Response::TransmitterCurrentBand(current) => self.band = Some(current.band),
+            Response::TransmitterFrequency(frequency) => {
+                self.frequency_hertz = Some(frequency.hertz)
+            }
+            Response::LowPassFilterSet(filter) => self.filter_bank = Some(filter.filter_bank),
+            Response::LockStatusGPS(status) => {
+                self.gps_locked = Some(matches!(status.lock, GpsLock::Locked))
+            }
+            Response::Locator4GPS(locator) => {
+                self.locator_4 = Some(locator.maidenhead_4.trim().to_string())
+            }
+            Response::Locator6GPS(locator) => {
+                self.locator_6 = Some(locator.maidenhead_6.trim().to_string())
+            }
+            Response::TransmitterWSPRSymbol(symbol) => {
+                if let Some(record) = self.current.as_mut() {
+                    record.band.get_or_insert(symbol.band);
+                    record.symbols = Some(symbol.symbol + 1);
+                }
+                return None;
+            }
+            _ => return None,
+        }
+        // Reports arriving after TON T still describe this transmission.
+        if let Some(record) = self.current.as_mut() {
+            record.band = self.band.or(record.band);
+            record.frequency_hertz = self.frequency_hertz.or(record.frequency_hertz);
+            record.filter_bank = self.filter_bank.or(record.filter_bank);
+        }
+        None
+    }
+
+    fn start(&self, time: DateTime<Utc>) -> TransmissionRecord {
+        TransmissionRecord {
+            start: time,
+            end: None,
+            band: self.band,
+            frequency_hertz: self.frequency_hertz,
+            filter_bank: self.filter_bank,
+            symbols: None,
+            gps_locked: self.gps_locked,
+            locator: self.locator_6.clone().or_else(|| self.locator_4.clone()),
+        }
+    }
+
+    pub fn is_transmitting(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// The transmission in progress, e.g. to log it on shutdown.
+    pub fn take_current(&mut self) -> Option<TransmissionRecord> {
+        self.current.take()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Csv,
+    JsonLines,
+}
+
+impl LogFormat {
+    /// Guess from a file's extension: ".csv" is CSV, anything else
+    /// JSON lines.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => LogFormat::Csv,
+            _ => LogFormat::JsonLines,
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "csv" => LogFormat::Csv,
+            "json" | "jsonl" => LogFormat::JsonLines,
+            _ => bail!("Unknown log format {s}, expected csv or jsonl"),
+        })
+    }
+}
+
+/// Writes serializable records one per line as CSV (with a header) or
+/// JSON lines, flushing after each so the log survives a crash.
+pub enum RecordWriter<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    JsonLines(W),
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(format: LogFormat, writer: W) -> Self {
+        Self::with_header(format, writer, true)
+    }
+
+    /// `header` is ignored for JSON lines; leave it off for CSV when
+    /// appending to an existing log.
+    pub fn with_header(format: LogFormat, writer: W, header: bool) -> Self {
+        match format {
+            LogFormat::Csv => RecordWriter::Csv(Box::new(
+                csv::WriterBuilder::new()
+                    .has_headers(header)
+                    .from_writer(writer),
+            )),
+            LogFormat::JsonLines => RecordWriter::JsonLines(writer),
+        }
+    }
+
+    pub fn write<T: Serialize>(&mut self, record: &T) -> Result<()> {
+        match self {
+            RecordWriter::Csv(writer) => {
+                writer.serialize(record)?;
+                writer.flush()?;
+            }
+            RecordWriter::JsonLines(writer) => {
+                serde_json::to_writer(&mut *writer, record)?;
+                writer.write_all(b"\n")?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read back a log written by `RecordWriter`. CSV logs must have a
+/// header.
+pub fn read_records<T: DeserializeOwned>(format: LogFormat, path: &Path) -> Result<Vec<T>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let records: Result<Vec<T>> = match format {
+        LogFormat::Csv => csv::Reader::from_reader(file)
+            .deserialize::<T>()
+            .map(|record| Ok(record?))
+            .collect(),
+        LogFormat::JsonLines => BufReader::new(file)
+            .lines()
+            .filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+            .map(|line| Ok(serde_json::from_str::<T>(&line?)?))
+            .collect(),
+    };
+    records.with_context(|| format!("Failed to read {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process_line;
+    use chrono::TimeZone;
+
+    fn response(line: &str) -> Response {
+        process_line(line.as_bytes().to_vec()).unwrap()
+    }
+
+    fn at(second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, second).unwrap()
+    }
+
+    fn feed(recorder: &mut TransmissionRecorder, lines: &[&str]) -> Vec<TransmissionRecord> {
+        lines
+            .iter()
+            .enumerate()
+            .filter_map(|(second, line)| recorder.update(&response(line), at(second as u32)))
+            .collect()
+    }
+
+    fn complete_transmission() -> TransmissionRecord {
+        let mut recorder = TransmissionRecorder::new();
+        let records = feed(
+            &mut recorder,
+            &[
+                "{GLC} T",
+                "{GL4} JO65",
+                "{GL6} JO65ab",
+                "{TBN} 06",
+                "{TON} T",
+                "{TFQ} 1409710000",
+                "{LPI} B",
+                "{TWS} 06 161",
+                "{TON} F",
+            ],
+        );
+        assert!(!recorder.is_transmitting());
+        assert_eq!(records.len(), 1);
+        records.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn records_a_complete_transmission() {
+        let record = complete_transmission();
+        assert_eq!(record.start, at(4));
+        assert_eq!(record.end, Some(at(8)));
+        assert_eq!(record.band, Some(Band::B20m));
+        assert_eq!(record.frequency_hertz, Some(14_097_100.));
+        assert_eq!(record.filter_bank, Some(FilterBank::B));
+        assert_eq!(record.gps_locked, Some(true));
+        assert_eq!(record.locator.as_deref(), Some("JO65ab"));
+        assert!(record.is_complete());
+    }
+
+    #[test]
+    fn interrupted_transmission_is_returned_incomplete() {
+        let mut recorder = TransmissionRecorder::new();
+        let records = feed(&mut recorder, &["{TON} T", "{TWS} 06 010", "{TON} T"]);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].end, None);
+        assert_eq!(records[0].band, Some(Band::B20m));
+        assert_eq!(records[0].symbols, Some(11));
+        assert!(!records[0].is_complete());
+        assert!(recorder.take_current().is_some());
+    }
+
+    #[test]
+    fn log_format_from_path() {
+        assert_eq!(LogFormat::for_path(Path::new("tx.CSV")), LogFormat::Csv);
+        assert_eq!(
+            LogFormat::for_path(Path::new("tx.jsonl")),
+            LogFormat::JsonLines
+        );
+        assert_eq!("jsonl".parse::<LogFormat>().unwrap(), LogFormat::JsonLines);
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let record = complete_transmission();
+        for format in [LogFormat::Csv, LogFormat::JsonLines] {
+            let path = std::env::temp_dir().join(format!(
+                "zachtek-transmissions-{}-{format:?}",
+                std::process::id()
+            ));
+            let mut writer = RecordWriter::new(format, File::create(&path).unwrap());
+            writer.write(&record).unwrap();
+            writer.write(&record).unwrap();
+            drop(writer);
+            let read: Result<Vec<TransmissionRecord>> = read_records(format, &path);
+            std::fs::remove_file(&path).unwrap();
+            let read = read.unwrap();
+            assert_eq!(read.len(), 2);
+            assert_eq!(read[1].start, record.start);
+            assert_eq!(read[1].end, record.end);
+            assert_eq!(read[1].band, record.band);
+            assert_eq!(read[1].filter_bank, record.filter_bank);
+            assert_eq!(read[1].locator, record.locator);
+            assert!(read[1].is_complete());
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use anyhow::{ensure, Result};
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::{Response, TimeSlot, TxPauseOption, ZachtekDevice, WSPR_TX_DURATION};
+
+pub const MAX_TX_PAUSE_MINUTES: u32 = 99_999;
+
+// Longest wait for a matching slot after a pause, for the 20 minute
+// schedules.
+const DEFAULT_SLOT_TOLERANCE: Duration = Duration::from_secs(20 * 60);
+
+/// Time between the starts of two consecutive transmissions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxGap {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    // Whether a band cycle completed (TCC) in between, i.e. whether
+    // the TX pause applies.
+    pub after_cycle: bool,
+}
+
+impl TxGap {
+    pub fn duration(&self) -> Duration {
+        (self.end - self.start).to_std().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseDeviation {
+    // Transmitted again before the pause was over.
+    TooOften,
+    // Waited longer than the pause plus a slot.
+    TooRarely,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PauseMismatch {
+    pub gap: TxGap,
+    pub tx_pause: Duration,
+    pub deviation: PauseDeviation,
+}
+
+impl fmt::Display for PauseMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?}: {:?} between transmissions at {} and {} with a TX pause of {:?}",
+            self.deviation,
+            self.gap.duration(),
+            self.gap.start,
+            self.gap.end,
+            self.tx_pause
+        )
+    }
+}
+
+/// Measures the gaps between transmissions (TON T) and compares them
+/// with the configured TX pause (OTP). In tracker mode (OTS) the unit
+/// only sends when it moves, so long gaps are expected.
+#[derive(Debug, Clone)]
+pub struct TxPauseTracker {
+    pub tx_pause: Option<Duration>,
+    pub time_slot: Option<TimeSlot>,
+    // How long after the pause the unit may wait for its next slot.
+    pub slot_tolerance: Duration,
+    pub gaps: Vec<TxGap>,
+    pub mismatches: Vec<PauseMismatch>,
+    last_start: Option<DateTime<Utc>>,
+    transmitting: bool,
+    cycle_complete: bool,
+}
+
+impl Default for TxPauseTracker {
+    fn default() -> Self {
+        Self {
+            tx_pause: None,
+            time_slot: None,
+            slot_tolerance: DEFAULT_SLOT_TOLERANCE,
+            gaps: Vec::new(),
+            mismatches: Vec::new(),
+            last_start: None,
+            transmitting: false,
+            cycle_complete: false,
+        }
+    }
+}
+
+impl TxPauseTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a response received at `time`. Returns the mismatch, if
+    /// this response starts a transmission too early or too late.
+    pub fn check(&mut self, response: &Response, time: DateTime<Utc>) -> Option<PauseMismatch> {
+        match response {
+            Response::TxPauseOption(option) => {
+                self.tx_pause = Some(option.duration);
+                None
+            }
+            Response::TimeSlotOption(option) => {
+                self.time_slot = Some(option.time_slot);
+                None
+            }
+            Response::TransmitterBandCycleComplete(_) => {
+                self.cycle_complete = true;
+                None
+            }
+            Response::TransmitterStatus(status) if !status.on => {
+                self.transmitting = false;
+                None
+            }
+            Response::TransmitterStatus(_) if !self.transmitting => {
+                self.transmitting = true;
+                self.start(time)
+            }
+            _ => None,
+        }
+    }
+
+    fn start(&mut self, time: DateTime<Utc>) -> Option<PauseMismatch> {
+        let last_start = self.last_start.replace(time)?;
+        let gap = TxGap {
+            start: last_start,
+            end: time,
+            after_cycle: std::mem::take(&mut self.cycle_complete),
+        };
+        self.gaps.push(gap);
+        let tx_pause = self.tx_pause?;
+        // The pause only applies after a cycle; within one the unit
+        // just waits for its next slot.
+        let pause = if gap.after_cycle {
+            tx_pause
+        } else {
+            Duration::ZERO
+        };
+        let shortest = WSPR_TX_DURATION + pause;
+        let longest = shortest + self.slot_tolerance;
+        let deviation = if gap.after_cycle && gap.duration() < shortest {
+            PauseDeviation::TooOften
+        } else if gap.duration() > longest && self.time_slot != Some(TimeSlot::Tracker) {
+            PauseDeviation::TooRarely
+        } else {
+            return None;
+        };
+        let mismatch = PauseMismatch {
+            gap,
+            tx_pause,
+            deviation,
+        };
+        warn!("{mismatch}");
+        self.mismatches.push(mismatch);
+        Some(mismatch)
+    }
+
+    /// Mean gap across band cycles, to compare with the pause.
+    pub fn mean_cycle_gap(&self) -> Option<Duration> {
+        let gaps: Vec<Duration> = self
+            .gaps
+            .iter()
+            .filter(|gap| gap.after_cycle)
+            .map(|gap| gap.duration())
+            .collect();
+        if gaps.is_empty() {
+            return None;
+        }
+        Some(gaps.iter().sum::<Duration>() / gaps.len() as u32)
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl ZachtekDevice {
+    pub fn read_tx_pause(&mut self) -> Result<Duration> {
+        self.query(TxPauseOption::CODE, b"", |response| match response {
+            Response::TxPauseOption(option) => Some(option.duration),
+            _ => None,
+        })
+    }
+
+    /// Set the TX pause (OTP), in whole minutes up to 99999.
+    pub fn write_tx_pause(&mut self, tx_pause: Duration) -> Result<()> {
+        let minutes = tx_pause.as_secs() / 60;
+        ensure!(
+            Duration::from_secs(minutes * 60) == tx_pause,
+            "TX pause {tx_pause:?} is not a whole number of minutes"
+        );
+        ensure!(
+            minutes <= MAX_TX_PAUSE_MINUTES as u64,
+            "TX pause of {minutes} minutes is longer than {MAX_TX_PAUSE_MINUTES}"
+        );
+        let args = format!("{minutes:05}");
+        self.set_verified(TxPauseOption::CODE, args.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TimeSlotOption, TransmitterBandCycleComplete, TransmitterStatus};
+    use chrono::TimeZone;
+
+    fn at(minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 0, minute, 0).unwrap()
+    }
+
+    fn tx_pause(minutes: u64) -> Response {
+        Response::TxPauseOption(TxPauseOption {
+            duration: Duration::from_secs(minutes * 60),
+        })
+    }
+
+    fn on(on: bool) -> Response {
+        Response::TransmitterStatus(TransmitterStatus { on })
+    }
+
+    fn cycle() -> Response {
+        Response::TransmitterBandCycleComplete(TransmitterBandCycleComplete {})
+    }
+
+    // A transmission starting at `minute`, with a band cycle completing
+    // before it if `after_cycle`.
+    fn transmit(
+        tracker: &mut TxPauseTracker,
+        minute: u32,
+        after_cycle: bool,
+    ) -> Option<PauseMismatch> {
+        if after_cycle {
+            tracker.check(&cycle(), at(minute));
+        }
+        let mismatch = tracker.check(&on(true), at(minute));
+        assert_eq!(tracker.check(&on(true), at(minute)), None);
+        tracker.check(&on(false), at(minute + 1));
+        mismatch
+    }
+
+    #[test]
+    fn pause_after_cycle_is_consistent() {
+        let mut tracker = TxPauseTracker::new();
+        tracker.check(&tx_pause(10), at(0));
+        assert_eq!(transmit(&mut tracker, 0, false), None);
+        assert_eq!(transmit(&mut tracker, 14, true), None);
+        assert_eq!(transmit(&mut tracker, 16, false), None);
+        assert_eq!(tracker.gaps.len(), 2);
+        assert_eq!(tracker.mean_cycle_gap(), Some(Duration::from_secs(14 * 60)));
+        assert!(tracker.is_consistent());
+    }
+
+    #[test]
+    fn early_transmission_after_cycle() {
+        let mut tracker = TxPauseTracker::new();
+        tracker.check(&tx_pause(10), at(0));
+        transmit(&mut tracker, 0, false);
+        let mismatch = transmit(&mut tracker, 4, true).unwrap();
+        assert_eq!(mismatch.deviation, PauseDeviation::TooOften);
+        assert_eq!(mismatch.gap.duration(), Duration::from_secs(4 * 60));
+    }
+
+    #[test]
+    fn late_transmission() {
+        let mut tracker = TxPauseTracker::new();
+        tracker.check(&tx_pause(0), at(0));
+        transmit(&mut tracker, 0, false);
+        let mismatch = transmit(&mut tracker, 40, false).unwrap();
+        assert_eq!(mismatch.deviation, PauseDeviation::TooRarely);
+    }
+
+    #[test]
+    fn tracker_mode_may_wait() {
+        let mut tracker = TxPauseTracker::new();
+        tracker.check(&tx_pause(0), at(0));
+        tracker.check(
+            &Response::TimeSlotOption(TimeSlotOption {
+                time_slot: TimeSlot::Tracker,
+            }),
+            at(0),
+        );
+        transmit(&mut tracker, 0, false);
+        assert_eq!(transmit(&mut tracker, 40, false), None);
+        // Still too often after a cycle.
+        tracker.check(&tx_pause(10), at(41));
+        assert!(transmit(&mut tracker, 44, true).is_some());
+    }
+
+    #[test]
+    fn unknown_pause_is_not_checked() {
+        let mut tracker = TxPauseTracker::new();
+        transmit(&mut tracker, 0, false);
+        assert_eq!(transmit(&mut tracker, 2, true), None);
+        assert_eq!(tracker.gaps.len(), 1);
+    }
+}
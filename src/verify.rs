@@ -0,0 +1,162 @@
+use anyhow::Result;
+use ascii::AsciiStr;
+use std::fmt;
+use tracing::{trace, warn};
+
+use crate::{is_timeout, BandTxEnable, LowPassFilterFactory, ZachtekDevice};
+
+// Attempts per setting before giving up.
+const SET_ATTEMPTS: usize = 3;
+
+// Lines read while waiting for an echo before giving up.
+const MAX_SKIPPED_LINES: usize = 64;
+
+// Codes whose first field selects which value is being set.
+const KEYED_CODES: &[&[u8]] = &[BandTxEnable::CODE, LowPassFilterFactory::CODE];
+
+/// Why a setting did not take effect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetError {
+    // The unit never echoed the code back.
+    NoEcho {
+        code: String,
+    },
+    // The unit echoed a different value, e.g. after rounding it or
+    // rejecting it and keeping the old one.
+    Mismatch {
+        code: String,
+        requested: String,
+        echoed: String,
+    },
+}
+
+impl fmt::Display for SetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetError::NoEcho { code } => write!(f, "{code} was not acknowledged"),
+            SetError::Mismatch {
+                code,
+                requested,
+                echoed,
+            } => write!(f, "{code} set to '{requested}' but unit has '{echoed}'"),
+        }
+    }
+}
+
+impl std::error::Error for SetError {}
+
+// Values are compared ignoring padding: whitespace around fields and
+// leading zeros on numbers.
+fn normalize(value: &str) -> Vec<String> {
+    value
+        .split_whitespace()
+        .map(|field| {
+            if field.bytes().all(|b| b.is_ascii_digit()) {
+                let trimmed = field.trim_start_matches('0');
+                if trimmed.is_empty() { "0" } else { trimmed }.to_string()
+            } else {
+                field.to_string()
+            }
+        })
+        .collect()
+}
+
+fn code_string(code: &[u8]) -> String {
+    String::from_utf8_lossy(code).into_owned()
+}
+
+impl ZachtekDevice {
+    /// Set a value and confirm the unit echoes it back unchanged,
+    /// retrying on mismatch. Failures are `SetError`s.
+    pub fn set_verified(&mut self, code: &[u8], value: &[u8]) -> Result<()> {
+        self.set_checked(code, value, SET_ATTEMPTS)
+    }
+
+    // `set_verified` for settings that act when received, e.g. MPS,
+    // which must not be sent twice.
+    pub(crate) fn set_verified_once(&mut self, code: &[u8], value: &[u8]) -> Result<()> {
+        self.set_checked(code, value, 1)
+    }
+
+    fn set_checked(&mut self, code: &[u8], value: &[u8], attempts: usize) -> Result<()> {
+        let requested = AsciiStr::from_ascii(value)?.to_string();
+        let mut error = SetError::NoEcho {
+            code: code_string(code),
+        };
+        // Keep the poll thread from asking for the old value, whose
+        // answer could be taken for the echo.
+        let write_lock = self.write_lock.clone();
+        let _guard = write_lock.lock().expect("Write lock poisoned.");
+        for attempt in 1..=attempts {
+            self.set_locked(code, value)?;
+            let echoed = match self.wait_for_echo(code, &requested)? {
+                Some(echoed) => echoed,
+                None => {
+                    warn!("{error} (attempt {attempt})");
+                    continue;
+                }
+            };
+            if normalize(&echoed) == normalize(&requested) {
+                return Ok(());
+            }
+            error = SetError::Mismatch {
+                code: code_string(code),
+                requested: requested.clone(),
+                echoed,
+            };
+            warn!("{error} (attempt {attempt})");
+        }
+        Err(error.into())
+    }
+
+    // Wait for "{CODE} value". For keyed codes (e.g. "04 E" for OBD)
+    // echoes for other keys are skipped.
+    fn wait_for_echo(&mut self, code: &[u8], requested: &str) -> Result<Option<String>> {
+        let requested = normalize(requested);
+        for _ in 0..MAX_SKIPPED_LINES {
+            let line = match self.read_line() {
+                Ok(line) => line,
+                Err(err) if is_timeout(&err) => return Ok(None),
+                Err(err) => return Err(err),
+            };
+            if line.len() < 5 || line[0] != b'{' || &line[1..4] != code || line[4] != b'}' {
+                trace!(
+                    "wait_for_echo: skipping {:?}",
+                    String::from_utf8_lossy(&line)
+                );
+                continue;
+            }
+            let echoed = String::from_utf8_lossy(line.get(6..).unwrap_or_default()).into_owned();
+            if KEYED_CODES.contains(&code) && normalize(&echoed).first() != requested.first() {
+                continue;
+            }
+            return Ok(Some(echoed));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn padding_is_ignored() {
+        assert_eq!(normalize("0000600"), normalize("600"));
+        assert_eq!(normalize(" 04  E "), normalize("4 E"));
+        assert_eq!(normalize("000"), vec!["0"]);
+    }
+
+    #[test]
+    fn values_still_differ() {
+        assert_ne!(normalize("23"), normalize("30"));
+        assert_ne!(normalize("04 E"), normalize("04 D"));
+        assert_ne!(normalize("JO65"), normalize("jo65"));
+    }
+
+    #[test]
+    fn non_numeric_fields_keep_zeros() {
+        assert_eq!(normalize("0A7"), vec!["0A7"]);
+        assert_eq!(normalize("SM0ABC"), vec!["SM0ABC"]);
+    }
+}
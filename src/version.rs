@@ -0,0 +1,104 @@
+use anyhow::Result;
+use std::fmt;
+use tracing::info;
+
+use crate::{
+    HardwareRevisionFactory, HardwareVersionFactory, ProductModel, Response,
+    SoftwareRevisionFactory, SoftwareVersionFactory, ZachtekDevice,
+};
+
+/// A version/revision pair as reported by FSV/FSR or FHV/FHR.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub version: u8,
+    pub revision: u8,
+}
+
+impl Version {
+    pub const fn new(version: u8, revision: u8) -> Self {
+        Self { version, revision }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}", self.version, self.revision)
+    }
+}
+
+/// What the handshake learned about a unit.
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub model: ProductModel,
+    pub firmware: Version,
+    pub hardware: Version,
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} firmware {} hardware {}",
+            self.model, self.firmware, self.hardware
+        )
+    }
+}
+
+impl ZachtekDevice {
+    /// Read model, firmware and hardware versions (FPN, FSV, FSR, FHV,
+    /// FHR). The API document (see `CODES`) doesn't say which firmware
+    /// added which codes, so the versions are only recorded; no command
+    /// is held back because of them.
+    pub fn handshake(&mut self) -> Result<DeviceInfo> {
+        self.info = None;
+        let model = self.read_model()?;
+        let firmware = Version::new(
+            self.query(
+                SoftwareVersionFactory::CODE,
+                b"",
+                |response| match response {
+                    Response::SoftwareVersionFactory(factory) => Some(factory.software_version),
+                    _ => None,
+                },
+            )?,
+            self.query(
+                SoftwareRevisionFactory::CODE,
+                b"",
+                |response| match response {
+                    Response::SoftwareRevisionFactory(factory) => Some(factory.software_revision),
+                    _ => None,
+                },
+            )?,
+        );
+        let hardware = Version::new(
+            self.query(
+                HardwareVersionFactory::CODE,
+                b"",
+                |response| match response {
+                    Response::HardwareVersionFactory(factory) => Some(factory.hardware_version),
+                    _ => None,
+                },
+            )?,
+            self.query(
+                HardwareRevisionFactory::CODE,
+                b"",
+                |response| match response {
+                    Response::HardwareRevisionFactory(factory) => Some(factory.hardware_revision),
+                    _ => None,
+                },
+            )?,
+        );
+        let info = DeviceInfo {
+            model,
+            firmware,
+            hardware,
+        };
+        info!("Connected to {info}");
+        self.info = Some(info.clone());
+        Ok(info)
+    }
+
+    pub fn info(&self) -> Option<&DeviceInfo> {
+        self.info.as_ref()
+    }
+}
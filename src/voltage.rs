@@ -0,0 +1,181 @@
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::fmt;
+use tracing::{info, warn};
+
+use crate::Response;
+
+// VCC the unit is designed to run at.
+pub const NOMINAL_MILLIVOLTS: u32 = 3300;
+
+// Samples kept for statistics. VCC is polled once a cycle, about every
+// 23 s at the default poll interval, so this is about six and a half
+// hours.
+const DEFAULT_HISTORY: usize = 1000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct VoltageThresholds {
+    pub nominal_millivolts: u32,
+    // Reported as a deviation beyond this much from nominal.
+    pub deviation_millivolts: u32,
+    // Reported as a brown-out at or below this.
+    pub brown_out_millivolts: u32,
+}
+
+impl Default for VoltageThresholds {
+    fn default() -> Self {
+        Self {
+            nominal_millivolts: NOMINAL_MILLIVOLTS,
+            deviation_millivolts: 150,
+            brown_out_millivolts: 3000,
+        }
+    }
+}
+
+impl VoltageThresholds {
+    pub fn classify(&self, millivolts: u32) -> VoltageState {
+        if millivolts <= self.brown_out_millivolts {
+            VoltageState::BrownOut
+        } else if millivolts.abs_diff(self.nominal_millivolts) > self.deviation_millivolts {
+            VoltageState::Deviating
+        } else {
+            VoltageState::Normal
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoltageState {
+    Normal,
+    Deviating,
+    BrownOut,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VoltageSample {
+    pub time: DateTime<Utc>,
+    pub millivolts: u32,
+}
+
+/// The voltage moved into a different state.
+#[derive(Debug, Clone, Copy)]
+pub struct VoltageEvent {
+    pub time: DateTime<Utc>,
+    pub millivolts: u32,
+    pub from: VoltageState,
+    pub to: VoltageState,
+}
+
+impl fmt::Display for VoltageEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: VCC {} mV, {:?} -> {:?}",
+            self.time, self.millivolts, self.from, self.to
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VoltageStats {
+    pub samples: usize,
+    pub min_millivolts: u32,
+    pub max_millivolts: u32,
+    pub mean_millivolts: f32,
+}
+
+impl fmt::Display for VoltageStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min {} mV, max {} mV, mean {:.0} mV over {} samples",
+            self.min_millivolts, self.max_millivolts, self.mean_millivolts, self.samples
+        )
+    }
+}
+
+/// Keeps a rolling history of the unit's supply voltage (MVC) and
+/// reports when it leaves or returns to the normal range.
+#[derive(Debug, Clone)]
+pub struct VoltageMonitor {
+    pub thresholds: VoltageThresholds,
+    pub state: VoltageState,
+    history: VecDeque<VoltageSample>,
+    capacity: usize,
+}
+
+impl Default for VoltageMonitor {
+    fn default() -> Self {
+        Self::new(VoltageThresholds::default())
+    }
+}
+
+impl VoltageMonitor {
+    pub fn new(thresholds: VoltageThresholds) -> Self {
+        Self::with_history(thresholds, DEFAULT_HISTORY)
+    }
+
+    pub fn with_history(thresholds: VoltageThresholds, capacity: usize) -> Self {
+        Self {
+            thresholds,
+            state: VoltageState::Normal,
+            history: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Feed a response received at `time`. Returns an event if this is
+    /// a voltage report that changes the state.
+    pub fn check(&mut self, response: &Response, time: DateTime<Utc>) -> Option<VoltageEvent> {
+        let Response::MicrocontrollerVoltage(voltage) = response else {
+            return None;
+        };
+        let millivolts = (voltage.voltage * 1000.).round() as u32;
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(VoltageSample { time, millivolts });
+
+        let state = self.thresholds.classify(millivolts);
+        if state == self.state {
+            return None;
+        }
+        let event = VoltageEvent {
+            time,
+            millivolts,
+            from: self.state,
+            to: state,
+        };
+        self.state = state;
+        match state {
+            VoltageState::Normal => info!("{event}"),
+            _ => warn!("{event}"),
+        }
+        Some(event)
+    }
+
+    pub fn history(&self) -> impl Iterator<Item = &VoltageSample> {
+        self.history.iter()
+    }
+
+    pub fn latest(&self) -> Option<&VoltageSample> {
+        self.history.back()
+    }
+
+    /// Statistics over the history, or `None` before the first sample.
+    pub fn stats(&self) -> Option<VoltageStats> {
+        let min_millivolts = self.history.iter().map(|sample| sample.millivolts).min()?;
+        let max_millivolts = self.history.iter().map(|sample| sample.millivolts).max()?;
+        let sum: u64 = self
+            .history
+            .iter()
+            .map(|sample| sample.millivolts as u64)
+            .sum();
+        Some(VoltageStats {
+            samples: self.history.len(),
+            min_millivolts,
+            max_millivolts,
+            mean_millivolts: sum as f32 / self.history.len() as f32,
+        })
+    }
+}